@@ -1,8 +1,19 @@
 use std::fmt::Display;
 
+use polars::prelude::{DataType, PolarsResult, Series};
 use serde::{Deserialize, Serialize};
 
-use crate::utils::stats_utils::Extrema;
+use crate::utils::stats_utils::SeriesStats;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistDistance {
+    /// total variation distance, half the L1 norm so the result is in `0..=1`
+    L1,
+    L2,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HistogramMismatchError;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct NDHistogram {
@@ -22,6 +33,34 @@ impl NDHistogram {
         self.borders.len()
     }
 
+    /// basket counts divided by the total count
+    pub fn normalized(&self) -> Vec<f64> {
+        let total = self.baskets.iter().sum::<usize>().max(1) as f64;
+        self.baskets.iter().map(|x| *x as f64 / total).collect()
+    }
+
+    /// distance between the normalized baskets, both histograms need the same borders
+    pub fn distance(
+        &self,
+        other: &NDHistogram,
+        metric: HistDistance,
+    ) -> Result<f64, HistogramMismatchError> {
+        if self.borders != other.borders || self.baskets.len() != other.baskets.len() {
+            return Err(HistogramMismatchError);
+        }
+        let diffs = self
+            .normalized()
+            .into_iter()
+            .zip(other.normalized())
+            .map(|(a, b)| a - b);
+        Ok(match metric {
+            HistDistance::L1 => diffs.map(f64::abs).sum::<f64>() / 2.0,
+            HistDistance::L2 => diffs.map(|x| x * x).sum::<f64>().sqrt(),
+        })
+    }
+
+    /// `data` has to be the measured values only. Missing samples must be dropped, not
+    /// filled with a default like `0.0` or `50.0`, those would show up as phantom counts.
     pub fn new(
         data: Vec<Vec<f64>>,
         n: usize,
@@ -44,38 +83,109 @@ impl NDHistogram {
 
         let limits = match limits {
             Some(l) => l,
-            None => (0..n).map(|_| None).collect(),
+            None => (0..data.len()).map(|_| None).collect(),
         };
 
+        // like `from_series`, NaN and infinite values don't stretch the borders
         let borders = data
             .iter()
-            .enumerate()
-            .map(|x| NDHistogram::gen_histogram_border(limits[x.0].unwrap_or(x.1.extrema()), n))
-            .collect::<Vec<Vec<f64>>>();
-
-        let mut baskets: Vec<usize> = vec![0; n.pow(data.len() as u32)];
-
-        let deltas = borders
-            .iter()
-            .map(|x| (0.00000001 + x.last().unwrap() - x.first().unwrap()).max(0.000001))
-            .collect::<Vec<f64>>();
-
-        let mut coords: Vec<usize> = vec![0; data.len()];
+            .zip(limits)
+            .map(|(x, limit)| {
+                limit
+                    .or_else(|| {
+                        x.iter()
+                            .filter(|x| x.is_finite())
+                            .fold(None, |e: Option<(f64, f64)>, x| match e {
+                                Some((min, max)) => Some((x.min(min), x.max(max))),
+                                None => Some((*x, *x)),
+                            })
+                    })
+                    .map(|e| NDHistogram::gen_histogram_border(e, n))
+            })
+            .collect::<Option<Vec<Vec<f64>>>>();
+        let borders = match borders {
+            Some(b) => b,
+            None => {
+                return NDHistogram {
+                    baskets: vec![],
+                    borders: vec![],
+                }
+            }
+        };
 
-        let max_index = baskets.len();
+        let mut builder = NDHistogramBuilder::new(borders);
+        let mut point: Vec<f64> = vec![0.0; data.len()];
 
         for i in 0..data.first().unwrap().len() {
             for d in 0..data.len() {
-                coords[d] = (((data[d][i] - borders[d].first().unwrap()) / deltas[d]) * (n as f64))
-                    .floor() as usize;
+                point[d] = data[d][i];
+            }
+            if point.iter().all(|x| x.is_finite()) {
+                builder.push(&point);
+            }
+        }
+
+        builder.finish()
+    }
+
+    /// Like [`NDHistogram::new`] without limits, but the borders come from the finite
+    /// extrema of each series and the points are streamed without collecting the data.
+    /// Rows with a null or non-finite value are skipped.
+    pub fn from_series(data: &[Series], n: usize) -> PolarsResult<NDHistogram> {
+        let columns = data
+            .iter()
+            .map(|x| x.cast(&DataType::Float64))
+            .collect::<PolarsResult<Vec<Series>>>()?;
+        let borders = columns
+            .iter()
+            .map(|x| {
+                x.finite_extrema()
+                    .map(|e| NDHistogram::gen_histogram_border(e, n))
+            })
+            .collect::<Option<Vec<Vec<f64>>>>();
+        let borders = match borders {
+            Some(b) if !b.is_empty() => b,
+            _ => {
+                return Ok(NDHistogram {
+                    baskets: vec![],
+                    borders: vec![],
+                })
             }
-            let index: usize = NDCoords(coords.clone(), n).into();
-            if index < max_index {
-                baskets[index] += 1;
+        };
+
+        let mut iters = columns
+            .iter()
+            .map(|x| x.f64().map(|x| x.into_iter()))
+            .collect::<PolarsResult<Vec<_>>>()?;
+        let mut builder = NDHistogramBuilder::new(borders);
+        while let Some(row) = iters
+            .iter_mut()
+            .map(|x| x.next())
+            .collect::<Option<Vec<Option<f64>>>>()
+        {
+            match row.into_iter().collect::<Option<Vec<f64>>>() {
+                Some(point) if point.iter().all(|x| x.is_finite()) => builder.push(&point),
+                _ => (),
             }
         }
+        Ok(builder.finish())
+    }
 
-        NDHistogram { baskets, borders }
+    /// one `border_<dim>` line with the bin edges per dimension followed by the
+    /// basket grid like in `Display`
+    pub fn to_csv_with_borders(&self) -> String {
+        self.borders
+            .iter()
+            .enumerate()
+            .map(|(d, b)| {
+                std::iter::once(format!("border_{}", d))
+                    .chain(b.iter().map(|x| x.to_string()))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            })
+            .chain(std::iter::once(self.to_string()))
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 
     fn gen_histogram_border(extrema: (f64, f64), n: usize) -> Vec<f64> {
@@ -105,6 +215,61 @@ impl Display for NDHistogram {
     }
 }
 
+/// Fills a histogram with fixed borders point by point, e.g. straight from a stream of
+/// angles, instead of collecting all data for [`NDHistogram::new`] first.
+#[derive(Debug, Clone)]
+pub struct NDHistogramBuilder {
+    baskets: Vec<usize>,
+    borders: Vec<Vec<f64>>,
+    deltas: Vec<f64>,
+    n: usize,
+}
+
+impl NDHistogramBuilder {
+    /// `borders` holds the `n + 1` bin edges of every dimension
+    pub fn new(borders: Vec<Vec<f64>>) -> NDHistogramBuilder {
+        let n = borders.first().map_or(0, |x| x.len().saturating_sub(1));
+        let deltas = borders
+            .iter()
+            .map(|x| (0.00000001 + x.last().unwrap() - x.first().unwrap()).max(0.000001))
+            .collect();
+        NDHistogramBuilder {
+            baskets: vec![0; n.pow(borders.len() as u32)],
+            borders,
+            deltas,
+            n,
+        }
+    }
+
+    /// adds one point with a value per dimension, points outside of the borders are dropped
+    pub fn push(&mut self, point: &[f64]) {
+        let n = self.n as f64;
+        let coords = point
+            .iter()
+            .zip(self.borders.iter().zip(self.deltas.iter()))
+            .map(|(x, (b, delta))| {
+                let c = (((x - b.first().unwrap()) / delta) * n).floor();
+                // checked per dimension, otherwise an overflow aliases into another basket
+                match c.is_finite() && c >= 0.0 && c < n {
+                    true => Some(c as usize),
+                    false => None,
+                }
+            })
+            .collect::<Option<Vec<usize>>>();
+        if let Some(coords) = coords {
+            let index: usize = NDCoords(coords, self.n).into();
+            self.baskets[index] += 1;
+        }
+    }
+
+    pub fn finish(self) -> NDHistogram {
+        NDHistogram {
+            baskets: self.baskets,
+            borders: self.borders,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct NDCoords(Vec<usize>, usize);
 
@@ -121,7 +286,125 @@ impl Into<usize> for NDCoords {
 
 #[cfg(test)]
 mod tests {
-    use crate::clustered_data::NDHistogram;
+    use polars::prelude::{NamedFrom, Series};
+
+    use crate::clustered_data::{
+        HistDistance, HistogramMismatchError, NDHistogram, NDHistogramBuilder,
+    };
+
+    fn hist(baskets: Vec<usize>) -> NDHistogram {
+        NDHistogram {
+            baskets,
+            borders: vec![vec![0.0, 1.0, 2.0]],
+        }
+    }
+
+    #[test]
+    fn distance() {
+        let a = hist(vec![4, 0]);
+        let b = hist(vec![0, 3]);
+
+        assert_eq!(a.distance(&hist(vec![2, 0]), HistDistance::L1), Ok(0.0));
+        assert_eq!(a.distance(&hist(vec![2, 0]), HistDistance::L2), Ok(0.0));
+        assert_eq!(a.distance(&b, HistDistance::L1), Ok(1.0));
+        assert_eq!(a.distance(&b, HistDistance::L2), Ok(2f64.sqrt()));
+
+        let other_grid = NDHistogram {
+            baskets: vec![1, 1],
+            borders: vec![vec![0.0, 2.0, 4.0]],
+        };
+        assert_eq!(
+            a.distance(&other_grid, HistDistance::L1),
+            Err(HistogramMismatchError)
+        );
+    }
+
+    #[test]
+    fn streamed_matches_batch() {
+        let x = (0..1000)
+            .map(|i| (i as f64 * 0.37).sin())
+            .collect::<Vec<f64>>();
+        let y = (0..1000)
+            .map(|i| (i as f64 * 0.11).cos() * 3.0)
+            .collect::<Vec<f64>>();
+
+        let batch = NDHistogram::new(vec![x.clone(), y.clone()], 4, None);
+
+        let mut builder = NDHistogramBuilder::new(batch.borders.clone());
+        x.iter()
+            .zip(y.iter())
+            .for_each(|(a, b)| builder.push(&[*a, *b]));
+
+        assert_eq!(builder.finish(), batch);
+    }
+
+    #[test]
+    fn from_series_matches_new() {
+        let x = (0..500)
+            .map(|i| (i as f64 * 0.3).sin())
+            .collect::<Vec<f64>>();
+        let y = (0..500)
+            .map(|i| (i as f64 * 0.05).cos())
+            .collect::<Vec<f64>>();
+
+        let h = NDHistogram::from_series(
+            &[Series::new("x", x.clone()), Series::new("y", y.clone())],
+            5,
+        )
+        .unwrap();
+
+        assert_eq!(h, NDHistogram::new(vec![x, y], 5, None));
+    }
+
+    #[test]
+    fn new_ignores_non_finite_values() {
+        let x = vec![0.0, 1.0, 2.0, 3.0, f64::INFINITY];
+
+        let h = NDHistogram::new(vec![x.clone()], 3, None);
+
+        assert_eq!(h.borders, vec![vec![0.0, 1.0, 2.0, 3.0]]);
+        assert_eq!(h.baskets.iter().sum::<usize>(), 4);
+        assert_eq!(
+            h,
+            NDHistogram::from_series(&[Series::new("x", x)], 3).unwrap()
+        );
+        assert_eq!(
+            NDHistogram::new(vec![vec![f64::NAN; 3]], 3, None),
+            NDHistogram {
+                baskets: vec![],
+                borders: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn builder_drops_points_outside_of_borders() {
+        let mut builder = NDHistogramBuilder::new(vec![vec![0.0, 1.0, 2.0], vec![0.0, 1.0, 2.0]]);
+
+        builder.push(&[0.5, 1.5]);
+        // would alias into the second row or run past the baskets without the range check
+        builder.push(&[0.5, 2.5]);
+        builder.push(&[2.5, 0.5]);
+        builder.push(&[-0.5, 0.5]);
+        builder.push(&[f64::INFINITY, 0.5]);
+        builder.push(&[0.5, f64::NAN]);
+
+        assert_eq!(builder.finish().baskets, vec![0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn csv_with_borders() {
+        let h = NDHistogram {
+            baskets: vec![1, 2, 3, 4],
+            borders: vec![vec![0.0, 1.0, 2.0], vec![0.0, 0.5, 1.0]],
+        };
+        let csv = h.to_csv_with_borders();
+        let lines = csv.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "border_0,0,1,2");
+        assert_eq!(lines[1], "border_1,0,0.5,1");
+        assert_eq!(lines[2..], ["1,2", "3,4"]);
+    }
 
     #[test]
     fn test() {
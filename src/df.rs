@@ -4,36 +4,67 @@ pub mod raw;
 pub mod score;
 pub mod time_bound_df;
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use polars::prelude::*;
 
 use uuid::Uuid;
 
+use std::collections::BTreeMap;
 use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::error::{DataError, DataResult};
 use crate::fs::{
     concat_csv_files, filter_files_by_date, find_uuid_dirs, list_files, parse_subdirs,
 };
 use crate::misc::{
-    get_num_of_sensors_from_file, infer_df_type, infer_file_type, is_new_schema,
-    parse_dart_timestring_short, read_first_line, read_first_n_chars,
+    get_num_of_sensors_from_file_with, get_number_of_csv_fields_with, has_csv_header_with,
+    infer_df_type, infer_file_type_with, is_csv_header, is_new_schema, parse_dart_timestring_short,
+    parse_iso_timestring, read_first_data_line_with, read_first_line, ISO_TIME_FORMAT,
+    ISO_TIME_OFFSET_FORMAT,
 };
 use crate::schema::{generate_flextail_schema, generate_points_schema, OutputType};
 
 use self::raw::transform_to_new_schema;
 
-#[derive(Debug)]
-enum TableFormat {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
     Csv,
+    /// gzip compressed csv, e.g. archived `.csv.gz` dumps
+    CsvGz,
     Arrow,
     Parquet,
 }
 
+impl TableFormat {
+    /// parquet, arrow ipc and gzip files start with magic bytes, everything else is taken
+    /// as csv
+    pub fn from_magic_bytes(bytes: &[u8]) -> TableFormat {
+        if bytes.starts_with(b"PAR1") {
+            TableFormat::Parquet
+        } else if bytes.starts_with(b"ARROW1") {
+            TableFormat::Arrow
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            TableFormat::CsvGz
+        } else {
+            TableFormat::Csv
+        }
+    }
+
+    /// detects the format of a file from its first bytes, see [`TableFormat::from_magic_bytes`]
+    pub fn from_file(path: &PathBuf) -> std::io::Result<TableFormat> {
+        let mut bytes = vec![];
+        File::open(path)?.take(6).read_to_end(&mut bytes)?;
+        Ok(TableFormat::from_magic_bytes(&bytes))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
-struct ParseOutputFormatError;
+pub struct ParseOutputFormatError;
 
 impl FromStr for TableFormat {
     type Err = ParseOutputFormatError;
@@ -47,24 +78,23 @@ impl FromStr for TableFormat {
         } {
             Some(file_ending) => match file_ending {
                 "csv" => Ok(TableFormat::Csv),
+                "gz" if s.ends_with(".csv.gz") => Ok(TableFormat::CsvGz),
                 "arrow" => Ok(TableFormat::Arrow),
                 "parquet" => Ok(TableFormat::Parquet),
                 _ => Err(ParseOutputFormatError),
             },
-            None => match read_first_n_chars(&s.to_string().into()).as_str() {
-                "PAR1" => Ok(TableFormat::Parquet),
-                "ARR1" => Ok(TableFormat::Arrow),
-                _ => match read_first_line(&s.to_string().into()) {
-                    Some(line) => {
-                        if line.contains(",") {
-                            Ok(TableFormat::Csv)
-                        } else {
-                            Err(ParseOutputFormatError)
-                        }
-                    }
-                    None => Err(ParseOutputFormatError),
-                },
-            },
+            None => {
+                let path = PathBuf::from(s);
+                match TableFormat::from_file(&path) {
+                    // anything without magic bytes, only taken as csv if it looks like one
+                    Ok(TableFormat::Csv) => match read_first_line(&path) {
+                        Some(line) if line.contains(",") => Ok(TableFormat::Csv),
+                        _ => Err(ParseOutputFormatError),
+                    },
+                    Ok(format) => Ok(format),
+                    Err(_) => Err(ParseOutputFormatError),
+                }
+            }
         }
     }
 }
@@ -80,72 +110,342 @@ impl ColNameGenerator {
     }
 }
 
-fn read_arrow_file(path: &PathBuf) -> PolarsResult<DataFrame> {
-    IpcReader::new(&mut std::fs::File::open(path).unwrap()).finish()
+/// Arrow IPC files keep their schema, only an `i64` time column of a points or raw frame
+/// is converted like in [`read_csv_file`].
+fn read_arrow_file(path: &PathBuf) -> DataResult<DataFrame> {
+    let mut df = IpcReader::new(File::open(path)?).finish()?;
+    Ok(match infer_df_type(&df) {
+        OutputType::points | OutputType::raw if df.column("t").is_ok() => {
+            convert_i64_to_time(&mut df, None)?
+        }
+        _ => df,
+    })
 }
 
-fn read_parquet_file(path: &PathBuf) -> PolarsResult<DataFrame> {
-    ParquetReader::new(&mut std::fs::File::open(path).unwrap())
+/// The csv readers detect header, schema and output type from the file itself, so gzip
+/// compressed files are decompressed into a temp file first.
+fn read_csv_gz_file(path: &PathBuf, options: &CsvReadOptions) -> DataResult<DataFrame> {
+    let mut temp = std::env::temp_dir();
+    temp.push(format!("{}.csv", Uuid::new_v4()));
+    let copied = std::io::copy(
+        &mut GzDecoder::new(File::open(path)?),
+        &mut File::create(&temp)?,
+    );
+    let df = match copied {
+        Ok(_) => read_csv_file_with(
+            &temp,
+            infer_file_type_with(&temp, options.delimiter),
+            options,
+        ),
+        Err(e) => Err(e.into()),
+    };
+    remove_temp_file(&temp);
+    df
+}
+
+fn read_parquet_file(path: &PathBuf) -> DataResult<DataFrame> {
+    Ok(ParquetReader::new(File::open(path)?)
         .read_parallel(ParallelStrategy::Auto)
-        .finish()
+        .finish()?)
 }
 
-fn any_value_to_i16(row: Vec<&AnyValue<'_>>) -> Vec<i16> {
-    row.into_iter()
-        .map(|x| match x {
-            AnyValue::Int32(v) => v.clone() as i16,
-            _ => {
-                println!("could not convert {}", x);
-                0
-            }
-        })
-        .collect()
+/// Reads all parquet files matching `pattern` (e.g. `points/*.parquet`) into one frame
+/// sorted by `t`. Without a match the frame is empty with the points schema, shards with
+/// different schemas are a `SchemaMismatch`.
+pub fn read_parquet_glob(pattern: &str) -> DataResult<DataFrame> {
+    let mut paths = glob::glob(pattern)
+        .map_err(|e| DataError::Parse(format!("invalid glob {pattern}: {e}")))?
+        .map(|x| x.map_err(|e| e.into_error()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    if paths.is_empty() {
+        let schema = OutputType::points
+            .schema(None)
+            .ok_or_else(|| DataError::Parse("no schema for points".into()))?;
+        return Ok(DataFrame::new(
+            schema
+                .iter_fields()
+                .map(|x| Series::new_empty(x.name(), x.data_type()))
+                .collect(),
+        )?);
+    }
+    let frames = paths
+        .iter()
+        .map(read_parquet_file)
+        .collect::<DataResult<Vec<DataFrame>>>()?;
+    Ok(concat_sorted(frames.iter())?)
+}
+
+pub fn read_input_file_into_df(path: PathBuf) -> DataResult<DataFrame> {
+    read_input_file_into_df_with(path, &CsvReadOptions::default())
 }
 
-pub fn read_input_file_into_df(path: PathBuf) -> PolarsResult<DataFrame> {
+/// `options` are only used for csv files
+pub fn read_input_file_into_df_with(
+    path: PathBuf,
+    options: &CsvReadOptions,
+) -> DataResult<DataFrame> {
+    // the format detection below expects an existing file
+    fs::metadata(&path)?;
     match TableFormat::from_str(&path.to_str().unwrap()) {
         Ok(format) => match format {
-            TableFormat::Csv => read_csv_file(&path, infer_file_type(&path)),
+            TableFormat::Csv => read_csv_file_with(
+                &path,
+                infer_file_type_with(&path, options.delimiter),
+                options,
+            ),
+            TableFormat::CsvGz => read_csv_gz_file(&path, options),
             TableFormat::Arrow => read_arrow_file(&path),
             TableFormat::Parquet => read_parquet_file(&path),
         },
-        Err(e) => Err(PolarsError::NoData(
-            format!("could not parse input file type {:?}", e).into(),
-        )),
+        Err(_) => Err(DataError::UnknownFormat(path)),
     }
 }
 
+/// Like [`read_input_file_into_df`] for data which is already in memory. Without a
+/// `format` it is detected from the magic bytes.
+pub fn read_bytes_into_df(bytes: &[u8], format: Option<TableFormat>) -> DataResult<DataFrame> {
+    let cursor = Cursor::new(bytes);
+    Ok(
+        match format.unwrap_or(TableFormat::from_magic_bytes(bytes)) {
+            TableFormat::Parquet => ParquetReader::new(cursor).finish(),
+            TableFormat::Arrow => IpcReader::new(cursor).finish(),
+            TableFormat::CsvGz => {
+                let mut csv = vec![];
+                GzDecoder::new(cursor).read_to_end(&mut csv)?;
+                return read_bytes_into_df(&csv, Some(TableFormat::Csv));
+            }
+            TableFormat::Csv => {
+                let header = match std::str::from_utf8(bytes) {
+                    Ok(s) => is_csv_header(s.lines().next().unwrap_or("")),
+                    Err(_) => false,
+                };
+                CsvReader::new(cursor).has_header(header).finish()
+            }
+        }?,
+    )
+}
+
 pub fn create_df_from_uuid(
     path: &PathBuf,
     uuid: &Uuid,
     output_type: OutputType,
     date: Option<NaiveDate>,
 ) -> PolarsResult<DataFrame> {
-    create_user_df(
+    create_df_from_uuid_with(path, uuid, output_type, date, &CsvReadOptions::default())
+}
+
+/// like [`create_df_from_uuid`] with the timezone and delimiter of `options`
+pub fn create_df_from_uuid_with(
+    path: &PathBuf,
+    uuid: &Uuid,
+    output_type: OutputType,
+    date: Option<NaiveDate>,
+    options: &CsvReadOptions,
+) -> PolarsResult<DataFrame> {
+    create_user_df_with(
         &find_uuid_dirs(&parse_subdirs(&path), uuid)
             .into_iter()
             .map(|x| x.path)
             .collect(),
         output_type,
         date,
+        options,
     )
 }
 
+/// Options for [`create_user_df_from_files_with`], the defaults match
+/// [`create_user_df_from_files`].
+#[derive(Debug, Clone, Default)]
+pub struct ConcatOptions {
+    /// keep the concatenated temp files for inspection instead of deleting them
+    pub keep_temp: bool,
+    /// timezone and delimiter of the csv files
+    pub csv: CsvReadOptions,
+}
+
 pub fn create_user_df_from_files(
     files: Vec<PathBuf>,
     output_type: OutputType,
     date: Option<NaiveDate>,
 ) -> PolarsResult<DataFrame> {
+    create_user_df_from_files_with(files, output_type, date, &ConcatOptions::default()).map(|x| x.0)
+}
+
+/// Also returns the paths of the temp files if they are kept. Parquet, arrow and gzip
+/// compressed csv files (e.g. in folders partially migrated from csv) are read on their
+/// own and concatenated with the csv files.
+pub fn create_user_df_from_files_with(
+    files: Vec<PathBuf>,
+    output_type: OutputType,
+    date: Option<NaiveDate>,
+    options: &ConcatOptions,
+) -> PolarsResult<(DataFrame, Vec<PathBuf>)> {
+    let mut kept = vec![];
     let files = if date.is_some() {
         filter_files_by_date(&files, date.unwrap())
     } else {
         files
     };
+    let (tables, files): (Vec<PathBuf>, Vec<PathBuf>) = files
+        .into_iter()
+        .partition(|x| matches!(TableFormat::from_file(x), Ok(f) if f != TableFormat::Csv));
+
+    let mut frames = read_table_files(&tables, output_type, &options.csv)?;
+    if frames.is_empty() {
+        let df = read_csv_files(files, output_type, options, &mut kept)?;
+        // the csv files are concatenated in the order they are listed
+        let df = match df.column("t") {
+            Ok(_) => df.sort(["t"], false)?,
+            Err(_) => df,
+        };
+        return Ok((df, kept));
+    }
+    if !files.is_empty() {
+        frames.push(read_csv_files(files, output_type, options, &mut kept)?);
+    }
+    Ok((concat_frames(frames, output_type)?, kept))
+}
+
+fn read_csv_files(
+    files: Vec<PathBuf>,
+    output_type: OutputType,
+    options: &ConcatOptions,
+    kept: &mut Vec<PathBuf>,
+) -> PolarsResult<DataFrame> {
+    // log messages can contain the delimiter, so only points and raw files are checked
+    let files = files
+        .into_iter()
+        .filter(|x| {
+            if output_type == OutputType::logs {
+                return true;
+            }
+            let inferred = infer_file_type_with(x, options.csv.delimiter);
+            if inferred != output_type {
+                println!(
+                    "skipping {:?}, expected {:?} but found {:?}",
+                    x, output_type, inferred
+                );
+            }
+            inferred == output_type
+        })
+        .collect::<Vec<PathBuf>>();
+
+    if output_type == OutputType::raw {
+        let mut groups: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
+        for file in files.iter() {
+            groups
+                .entry(get_number_of_csv_fields_with(file, options.csv.delimiter))
+                .or_default()
+                .push(file.clone());
+        }
+        if groups.len() > 1 {
+            return read_raw_files_aligned(groups.into_values().collect(), options, kept);
+        }
+    }
+
+    read_concatenated(&files, options, kept)
+}
+
+/// reads parquet, arrow and gzip compressed csv files, frames of another output type are
+/// skipped
+fn read_table_files(
+    files: &[PathBuf],
+    output_type: OutputType,
+    csv: &CsvReadOptions,
+) -> PolarsResult<Vec<DataFrame>> {
+    let mut frames = vec![];
+    for file in files {
+        let df = match TableFormat::from_file(file)? {
+            TableFormat::Arrow => read_arrow_file(file)?,
+            TableFormat::CsvGz => read_csv_gz_file(file, csv)?,
+            _ => read_parquet_file(file)?,
+        };
+        let inferred = infer_df_type(&df);
+        if inferred == output_type {
+            frames.push(df);
+        } else {
+            println!(
+                "skipping {:?}, expected {:?} but found {:?}",
+                file, output_type, inferred
+            );
+        }
+    }
+    Ok(frames)
+}
+
+/// raw csv files are read in the old schema, they are converted if another frame is in the
+/// new one
+fn concat_frames(frames: Vec<DataFrame>, output_type: OutputType) -> PolarsResult<DataFrame> {
+    let frames = if output_type == OutputType::raw && frames.iter().any(is_new_schema) {
+        frames
+            .into_iter()
+            .map(|mut x| match is_new_schema(&x) {
+                true => Ok(x),
+                false => transform_to_new_schema(&mut x),
+            })
+            .collect::<PolarsResult<Vec<DataFrame>>>()?
+    } else {
+        frames
+    };
+    concat_sorted(frames.iter())
+}
+
+fn read_concatenated(
+    files: &Vec<PathBuf>,
+    options: &ConcatOptions,
+    kept: &mut Vec<PathBuf>,
+) -> PolarsResult<DataFrame> {
+    let new_path = concat_csv_files(files).map_err(DataError::Io)?;
+    let df = read_input_file_into_df_with(new_path.clone(), &options.csv);
+    if options.keep_temp {
+        kept.push(new_path);
+    } else {
+        remove_temp_file(&new_path);
+    }
+    Ok(df?)
+}
+
+/// best-effort, a temp file which can't be deleted is left behind without affecting the result
+fn remove_temp_file(path: &PathBuf) {
+    let _ = fs::remove_file(path);
+}
+
+/// Raw files with different sensor counts (e.g. after a hardware swap) can't be parsed as
+/// one csv. Each group is read on its own and aligned to the columns of the group with the
+/// most sensors, missing sensors are null.
+fn read_raw_files_aligned(
+    groups: Vec<Vec<PathBuf>>,
+    options: &ConcatOptions,
+    kept: &mut Vec<PathBuf>,
+) -> PolarsResult<DataFrame> {
+    let frames = groups
+        .iter()
+        .map(|x| read_concatenated(x, options, kept))
+        .collect::<PolarsResult<Vec<DataFrame>>>()?;
+    let names: Vec<String> = match frames.iter().max_by_key(|x| x.width()) {
+        Some(df) => df
+            .get_column_names()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        None => return Ok(DataFrame::default()),
+    };
 
-    let new_path = concat_csv_files(&files);
-    let df = read_input_file_into_df(new_path.clone());
-    fs::remove_file(new_path).expect("could not delete file");
-    return df;
+    let aligned = frames
+        .into_iter()
+        .map(|mut df| {
+            for name in names.iter() {
+                if df.column(name).is_err() {
+                    df.with_column(Series::full_null(name, df.height(), &DataType::Int32))?;
+                }
+            }
+            df.select(&names)
+        })
+        .collect::<PolarsResult<Vec<DataFrame>>>()?;
+    // the groups are ordered by sensor count, not by time
+    concat_sorted(aligned.iter())
 }
 
 pub fn create_user_df(
@@ -153,7 +453,17 @@ pub fn create_user_df(
     output_type: OutputType,
     date: Option<NaiveDate>,
 ) -> PolarsResult<DataFrame> {
-    create_user_df_from_files(
+    create_user_df_with(folders, output_type, date, &CsvReadOptions::default())
+}
+
+/// like [`create_user_df`] with the timezone and delimiter of `options`
+pub fn create_user_df_with(
+    folders: &Vec<PathBuf>,
+    output_type: OutputType,
+    date: Option<NaiveDate>,
+    options: &CsvReadOptions,
+) -> PolarsResult<DataFrame> {
+    create_user_df_from_files_with(
         folders
             .iter()
             .map(|x| {
@@ -165,140 +475,266 @@ pub fn create_user_df(
             .collect(),
         output_type,
         date,
+        &ConcatOptions {
+            csv: options.clone(),
+            ..Default::default()
+        },
     )
+    .map(|x| x.0)
 }
 
-fn flatten_df(df: DataFrame) -> Result<DataFrame, PolarsError> {
-    let mut lazyframe = df.lazy();
-    let left: Vec<String> = (1..=18).into_iter().map(|x| format!("l{}", x)).collect();
-    let right: Vec<String> = (1..=18).into_iter().map(|x| format!("r{}", x)).collect();
-    let bend: Vec<String> = (1..=18)
+fn flatten_df(df: DataFrame, n_sensors: usize) -> Result<DataFrame, PolarsError> {
+    let present: Vec<String> = df
+        .get_column_names()
         .into_iter()
-        .map(|x| format!("bend_{}", x))
-        .collect();
-    let twist: Vec<String> = (1..=18)
-        .into_iter()
-        .map(|x| format!("twist_{}", x))
+        .map(String::from)
         .collect();
+    let mut lazyframe = df.lazy();
+    let left: Vec<String> = (1..=n_sensors).map(|x| format!("l{}", x)).collect();
+    let right: Vec<String> = (1..=n_sensors).map(|x| format!("r{}", x)).collect();
+    let bend: Vec<String> = (1..=n_sensors).map(|x| format!("bend_{}", x)).collect();
+    let twist: Vec<String> = (1..=n_sensors).map(|x| format!("twist_{}", x)).collect();
     let acc: Vec<String> = ('x'..='z').into_iter().map(|x| x.to_string()).collect();
     let gyro: Vec<String> = vec!["ɑ", "β", "ɣ"]
         .into_iter()
         .map(|x| x.to_string())
         .collect();
 
-    let column_names: Vec<&str> = vec!["left", "right", "acc", "gyro", "alpha", "beta"];
+    let column_names: Vec<&str> = vec!["left", "right", "acc", "gyro", "alpha", "beta"]
+        .into_iter()
+        .filter(|x| present.iter().any(|p| p == x))
+        .collect();
 
-    for pair in column_names
-        .clone()
+    for pair in vec!["left", "right", "acc", "gyro", "alpha", "beta"]
         .into_iter()
         .zip(vec![left, right, acc, gyro, bend, twist])
-        .into_iter()
+        .filter(|(name, _)| column_names.contains(name))
     {
-        for (index, ch) in pair.1.iter().enumerate().take(pair.1.len()) {
-            lazyframe = lazyframe.with_columns([col(pair.0)
-                .arr()
-                .get(lit(index as i64))
-                .alias(&ch.to_string())])
+        for (index, ch) in pair.1.iter().enumerate() {
+            lazyframe = lazyframe.with_columns([col(pair.0).arr().get(lit(index as i64)).alias(ch)])
         }
     }
 
-    let lazyframe = lazyframe.drop_columns(column_names);
-    let lazyframe = lazyframe.drop_columns(["coords"]);
+    let mut lazyframe = lazyframe.drop_columns(column_names);
+    if present.iter().any(|x| x == "coords") {
+        lazyframe = lazyframe.drop_columns(["coords"]);
+    }
 
     lazyframe.collect()
 }
 
-fn write_flat_df(path: &PathBuf, df: DataFrame) {
-    match flatten_df(df) {
-        Ok(df) => {
-            let file = &mut File::create(path).expect("could not create file");
-            match CsvWriter::new(file)
-                .has_header(false)
-                .finish(&mut df.clone())
+/// length of the first non null list in `column`
+fn first_list_len(df: &DataFrame, column: &str) -> Option<usize> {
+    df.column(column)
+        .ok()?
+        .list()
+        .ok()?
+        .into_iter()
+        .flatten()
+        .next()
+        .map(|x| x.len())
+}
+
+/// Writes a raw frame with one csv column per sensor value (`l1`..`ln`, `r1`..`rn`,
+/// `x`, `y`, `z`, `ɑ`, `β`, `ɣ`, ...). List columns missing from `df` are skipped.
+pub fn write_flat_df(
+    path: &PathBuf,
+    df: DataFrame,
+    n_sensors: usize,
+    options: &WriteOptions,
+) -> DataResult<()> {
+    let mut df = flatten_df(df, n_sensors)?;
+    let file = File::create(path)?;
+    Ok(CsvWriter::new(file)
+        .has_header(options.csv_header)
+        .finish(&mut df)?)
+}
+
+/// Options for [`write_df_with_options`]; the defaults match [`write_df`].
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// write the column names as the first csv line
+    pub csv_header: bool,
+    pub parquet: ParquetWriteOptions,
+}
+
+/// Passed to the `ParquetWriter`, e.g. zstd at a higher level for archives and
+/// uncompressed or snappy for frames which are reloaded often.
+#[derive(Debug, Clone)]
+pub struct ParquetWriteOptions {
+    /// `None` keeps the default compression of polars
+    pub compression: Option<ParquetCompression>,
+    pub statistics: bool,
+    /// `None` writes a single row group
+    pub row_group_size: Option<usize>,
+}
+
+impl Default for ParquetWriteOptions {
+    fn default() -> Self {
+        ParquetWriteOptions {
+            compression: None,
+            statistics: true,
+            row_group_size: None,
+        }
+    }
+}
+
+impl ParquetWriteOptions {
+    fn writer<W: Write>(&self, writer: W) -> ParquetWriter<W> {
+        let writer = ParquetWriter::new(writer)
+            .with_statistics(self.statistics)
+            .with_row_group_size(self.row_group_size);
+        match self.compression {
+            Some(compression) => writer.with_compression(compression),
+            None => writer,
+        }
+    }
+}
+
+pub fn write_df(path: &PathBuf, df: &mut DataFrame) -> DataResult<()> {
+    write_df_with_options(path, df, &WriteOptions::default())
+}
+
+/// The format is taken from the file ending, a path without a known ending is
+/// [`DataError::UnknownFormat`].
+pub fn write_df_with_options(
+    path: &PathBuf,
+    df: &mut DataFrame,
+    options: &WriteOptions,
+) -> DataResult<()> {
+    // resolved first, an unknown ending must not leave an empty file behind
+    let format = match TableFormat::from_str(path.to_str().unwrap()) {
+        Ok(format) => format,
+        Err(_) => return Err(DataError::UnknownFormat(path.clone())),
+    };
+    let file = &mut File::create(path)?;
+    match format {
+        TableFormat::Csv => {
+            let mut df = df.clone();
+            if CsvWriter::new(file)
+                .has_header(options.csv_header)
+                .finish(&mut df)
+                .is_err()
             {
-                Ok(_) => println!("wrote file to {:?}", path),
-                Err(e) => {
-                    println!("could no write df 1 {e}")
-                }
+                // list columns of the new schema can't be written as csv
+                let n_sensors = first_list_len(&df, "left").unwrap_or(18);
+                write_flat_df(path, df, n_sensors, options)?;
             }
         }
-        Err(e) => println!("could no write df 2 {e}"),
+        TableFormat::CsvGz => {
+            CsvWriter::new(GzEncoder::new(file, Compression::default()))
+                .has_header(options.csv_header)
+                .finish(df)?;
+        }
+        TableFormat::Parquet => {
+            options
+                .parquet
+                .writer(file)
+                .finish(&mut to_table_schema(df)?)?;
+        }
+        TableFormat::Arrow => {
+            IpcWriter::new(file).finish(&mut to_table_schema(df)?)?;
+        }
     }
+    println!("wrote file to {:?}", path);
+    Ok(())
 }
 
-pub fn write_df(path: &PathBuf, df: &mut DataFrame) {
-    let file = &mut File::create(path).expect("could not create file");
-    match TableFormat::from_str(path.to_str().unwrap()) {
-        Ok(e) => match e {
-            TableFormat::Csv => {
-                if let Some(mut df) = Some(df.clone()) {
-                    match CsvWriter::new(file).has_header(false).finish(&mut df) {
-                        Ok(_) => println!("wrote file to {:?}", path),
-                        _ => write_flat_df(path, df),
-                    }
-                } else {
-                    match CsvWriter::new(file).has_header(false).finish(df) {
-                        Ok(_) => println!("wrote file to {:?}", path),
-                        Err(e) => {
-                            println!("could no write df {e}")
-                        }
-                    }
-                }
-            }
-            TableFormat::Parquet => {
-                let mut df = if (!is_new_schema(&df))
-                    && match infer_df_type(&df) {
-                        OutputType::raw => true,
-                        _ => false,
-                    } {
-                    transform_to_new_schema(df).unwrap()
-                } else {
-                    match infer_df_type(&df) {
-                        OutputType::points | OutputType::logs => df.clone(),
-                        OutputType::raw => match convert_i64_to_time(df, None) {
-                            Ok(df) => df.clone(),
-                            Err(_) => df.clone(),
-                        },
-                    }
-                };
-                match ParquetWriter::new(file)
-                    .with_statistics(true)
-                    .finish(&mut df)
-                {
-                    Ok(_) => println!("wrote df {:?}\n file to {:?}", df, path),
-                    Err(_) => println!("failed to write file"),
-                }
-            }
-            TableFormat::Arrow => {
-                let mut df = if (!is_new_schema(&df))
-                    && match infer_df_type(&df) {
-                        OutputType::raw => true,
-                        _ => false,
-                    } {
-                    transform_to_new_schema(df).unwrap()
-                } else {
-                    match infer_df_type(&df) {
-                        OutputType::points | OutputType::logs => df.clone(),
-                        OutputType::raw => match convert_i64_to_time(df, None) {
-                            Ok(df) => df.clone(),
-                            _ => df.clone(),
-                        },
-                    }
-                };
-                match IpcWriter::new(file).finish(&mut df) {
-                    Ok(_) => println!("wrote df {:?}\n file to {:?}", df, path),
-                    Err(e) => println!("failed to write file because {e}"),
-                }
-            }
+/// parquet and arrow files hold raw frames in the new schema with a datetime `t`
+fn to_table_schema(df: &mut DataFrame) -> PolarsResult<DataFrame> {
+    Ok(match infer_df_type(df) {
+        OutputType::raw if !is_new_schema(df) => transform_to_new_schema(df)?,
+        OutputType::raw => match convert_i64_to_time(df, None) {
+            Ok(df) => df,
+            Err(_) => df.clone(),
         },
-        Err(_) => todo!(),
+        OutputType::points | OutputType::logs => df.clone(),
+    })
+}
+
+/// Stacks frames with identical schemas and sorts the result by `t`.
+pub(crate) fn concat_sorted<'a, I>(frames: I) -> PolarsResult<DataFrame>
+where
+    I: IntoIterator<Item = &'a DataFrame>,
+{
+    let mut frames = frames.into_iter();
+    let mut df = match frames.next() {
+        Some(df) => df.clone(),
+        None => return Err(PolarsError::NoData("no frames to concatenate".into())),
+    };
+    for other in frames {
+        if other.schema() != df.schema() {
+            return Err(PolarsError::SchemaMismatch(
+                format!(
+                    "cannot concatenate {:?} and {:?}",
+                    df.schema(),
+                    other.schema()
+                )
+                .into(),
+            ));
+        }
+        df.vstack_mut(other)?;
+    }
+    df.sort(["t"], false)
+}
+
+/// rows serialized at once by [`write_ndjson`]
+const NDJSON_CHUNK_ROWS: usize = 10_000;
+
+/// One json object per row, `t` is written as ms since epoch like in the `*DfJS` types.
+/// The frame is serialized in chunks of rows, so only a chunk is held in memory besides `df`.
+pub fn write_ndjson<W: Write>(df: &DataFrame, mut writer: W) -> PolarsResult<()> {
+    for offset in (0..df.height()).step_by(NDJSON_CHUNK_ROWS) {
+        let mut chunk = df.slice(offset as i64, NDJSON_CHUNK_ROWS);
+        if let Ok(t) = chunk.column("t") {
+            let t = t.cast(&DataType::Int64)?;
+            chunk.with_column(t)?;
+        }
+        JsonWriter::new(&mut writer)
+            .with_json_format(JsonFormat::JsonLines)
+            .finish(&mut chunk)?;
     }
+    Ok(())
 }
 
+pub(crate) fn ndjson_string(df: &DataFrame) -> PolarsResult<String> {
+    let mut buf: Vec<u8> = vec![];
+    write_ndjson(df, &mut buf)?;
+    String::from_utf8(buf).map_err(|e| PolarsError::ComputeError(format!("{}", e).into()))
+}
+
+/// Unpivots list columns (e.g. `left`, `right`, `acc`, `gyro`) into rows with the
+/// columns `t`, `column`, `index` (position inside the list) and `value`.
+pub fn melt_sensor_lists(df: &DataFrame, columns: &[&str]) -> PolarsResult<DataFrame> {
+    let mut melted = DataFrame::default();
+    for name in columns {
+        let list = df.column(name)?.list()?;
+
+        let mut value = list.clone().into_series();
+        value.rename("value");
+        let mut index = ListChunked::from_iter(
+            list.into_iter()
+                .map(|x| x.map(|x| Series::new("", (0..x.len() as u32).collect::<Vec<u32>>()))),
+        )
+        .into_series();
+        index.rename("index");
+
+        let mut part = DataFrame::new(vec![df.column("t")?.clone(), value, index])?
+            .explode(["value", "index"])?;
+        part.with_column(Series::new("column", vec![*name; part.height()]))?;
+        let part = part.select(["t", "column", "index", "value"])?;
+
+        if melted.width() == 0 {
+            melted = part;
+        } else {
+            melted.vstack_mut(&part)?;
+        }
+    }
+    Ok(melted)
+}
+
+#[deprecated(note = "points csv files with ISO 8601 times are parsed directly now")]
 pub fn convert_time_to_i64(df: &mut DataFrame, column: Option<&str>) -> Option<DataFrame> {
-    // TODO the polars parser doesn't recognize iso 8601 while parsing
-    // therefore the time strings are converted back to i64, which is stupid
-    // but otherwise the csv can't be parsed again
     if let Ok(col) = df.column(column.unwrap_or("t")) {
         if let Ok(col) = col.cast(&DataType::Int64) {
             return df.clone().with_column(col).ok().cloned();
@@ -307,9 +743,38 @@ pub fn convert_time_to_i64(df: &mut DataFrame, column: Option<&str>) -> Option<D
     None
 }
 
+/// timezone the `t` column is cast to unless configured otherwise
+pub const DEFAULT_TIMEZONE: &str = "Europe/Berlin";
+
+#[derive(Debug, Clone)]
+pub struct CsvReadOptions {
+    /// timezone of the resulting `t` column
+    pub timezone: String,
+    /// field separator, some older exporters used `b';'`
+    pub delimiter: u8,
+}
+
+impl Default for CsvReadOptions {
+    fn default() -> Self {
+        CsvReadOptions {
+            timezone: DEFAULT_TIMEZONE.to_string(),
+            delimiter: b',',
+        }
+    }
+}
+
 pub fn convert_i64_to_time(
     df: &mut DataFrame,
     time_unit: Option<TimeUnit>,
+) -> PolarsResult<DataFrame> {
+    convert_i64_to_time_tz(df, time_unit, None)
+}
+
+/// same as [`convert_i64_to_time`] but with a timezone, `None` falls back to `DEFAULT_TIMEZONE`
+pub fn convert_i64_to_time_tz(
+    df: &mut DataFrame,
+    time_unit: Option<TimeUnit>,
+    tz: Option<&str>,
 ) -> PolarsResult<DataFrame> {
     if let Err(_) = df.column("t").unwrap().i64() {
         return Ok(df.clone());
@@ -326,81 +791,262 @@ pub fn convert_i64_to_time(
     Ok(df
         .with_column(df.column("t")?.cast(&DataType::Datetime(
             time_unit.unwrap_or(polars::prelude::TimeUnit::Milliseconds),
-            Some("Europe/Berlin".into()),
+            Some(tz.unwrap_or(DEFAULT_TIMEZONE).into()),
         ))?)?
         .clone())
 }
 
-pub fn read_points_csv(path: &PathBuf) -> PolarsResult<DataFrame> {
-    convert_i64_to_time(
-        &mut CsvReader::from_path(path)?
-            .with_schema(Arc::new(generate_points_schema()))
-            .with_ignore_errors(true)
-            .has_header(false)
-            .finish()?,
-        None,
-    )
+/// checks if the time column of the first line holds an ISO 8601 string instead of ms
+fn has_iso_time(path: &PathBuf, output_type: &OutputType, delimiter: u8) -> bool {
+    match read_first_data_line_with(path, delimiter) {
+        Some(line) => {
+            let field = match output_type {
+                OutputType::raw => line.trim().split(delimiter as char).last(),
+                _ => line.split(delimiter as char).next(),
+            };
+            match field {
+                Some(field) => {
+                    field.trim().parse::<i64>().is_err() && parse_iso_timestring(field).is_ok()
+                }
+                None => false,
+            }
+        }
+        None => false,
+    }
 }
 
-pub fn read_logs_csv(path: &PathBuf) -> PolarsResult<DataFrame> {
-    let df = CsvReader::from_path(path)?
+/// Parses a utf8 `t` column of ISO 8601 strings into a `Datetime` column in `tz`, strings
+/// which can't be parsed are null. Times with an offset (`Z`, `+02:00`) are converted to
+/// UTC, times without one are taken as UTC.
+fn iso_time_expr(tz: &str) -> Expr {
+    let strptime = |t: Expr, format: &str, tz_aware: bool| {
+        t.str().strptime(
+            DataType::Datetime(TimeUnit::Milliseconds, None),
+            StrptimeOptions {
+                format: Some(format.into()),
+                strict: false,
+                exact: true,
+                tz_aware,
+                utc: tz_aware,
+                ..Default::default()
+            },
+        )
+    };
+    let t = col("t").str().strip(None);
+    // `%:z` doesn't match `Z`
+    let with_offset = strptime(
+        t.clone().str().replace(lit("Z$"), lit("+00:00"), false),
+        ISO_TIME_OFFSET_FORMAT,
+        true,
+    );
+    let without_offset = strptime(t, ISO_TIME_FORMAT, false)
+        .dt()
+        .replace_time_zone(Some("UTC".into()));
+
+    with_offset
+        .fill_null(without_offset)
+        .dt()
+        .convert_time_zone(tz.into())
+        .alias("t")
+}
+
+/// rows which can't be parsed are dropped, see [`iso_time_expr`]
+fn parse_iso_time(df: DataFrame, tz: &str) -> PolarsResult<DataFrame> {
+    df.lazy()
+        .with_column(iso_time_expr(tz))
+        .filter(col("t").is_not_null())
+        .collect()
+}
+
+/// `Ok(true)` for a header line naming a `t` column, a header without it is an error
+fn check_csv_header(path: &PathBuf, delimiter: u8) -> DataResult<bool> {
+    if !has_csv_header_with(path, delimiter) {
+        return Ok(false);
+    }
+    match read_first_line(path) {
+        Some(line)
+            if line
+                .trim()
+                .split(delimiter as char)
+                .any(|x| x.trim().trim_matches('"') == "t") =>
+        {
+            Ok(true)
+        }
+        _ => Err(DataError::Parse(format!(
+            "the header of {:?} has no t column",
+            path
+        ))),
+    }
+}
+
+/// single rows with invalid timestamps are dropped, but not a whole file of `read` rows
+fn check_timestamps(df: DataFrame, read: usize, path: &PathBuf) -> DataResult<DataFrame> {
+    if read > 0 && df.height() == 0 {
+        return Err(DataError::Parse(format!(
+            "no valid timestamp in {:?}",
+            path
+        )));
+    }
+    Ok(df)
+}
+
+/// reads a points csv, a leading header line (`t,score,...`) is detected and skipped
+pub fn read_points_csv(path: &PathBuf) -> DataResult<DataFrame> {
+    read_points_csv_with(path, &CsvReadOptions::default())
+}
+
+pub fn read_points_csv_with(path: &PathBuf, options: &CsvReadOptions) -> DataResult<DataFrame> {
+    let iso_time = has_iso_time(path, &OutputType::points, options.delimiter);
+    let mut schema = generate_points_schema();
+    if iso_time {
+        schema.with_column("t".into(), DataType::Utf8);
+    }
+
+    let mut df = CsvReader::new(File::open(path)?)
+        .with_schema(Arc::new(schema))
+        .with_delimiter(options.delimiter)
+        .with_ignore_errors(true)
+        .has_header(check_csv_header(path, options.delimiter)?)
+        .finish()?;
+    let read = df.height();
+    let df = match iso_time {
+        true => parse_iso_time(df, &options.timezone)?,
+        false => convert_i64_to_time_tz(&mut df, None, Some(&options.timezone))?,
+    };
+    check_timestamps(df, read, path)
+}
+
+pub fn read_logs_csv(path: &PathBuf) -> DataResult<DataFrame> {
+    read_logs_csv_with(path, &CsvReadOptions::default())
+}
+
+/// the timezone of `options` isn't used, log times are kept as they are
+pub fn read_logs_csv_with(path: &PathBuf, options: &CsvReadOptions) -> DataResult<DataFrame> {
+    let df = CsvReader::new(File::open(path)?)
+        .with_delimiter(options.delimiter)
         .with_ignore_errors(true)
         .with_schema(Arc::new(OutputType::logs.schema(None).unwrap()))
         .has_header(false)
         .finish()?;
 
-    // ass
-    let mut df = df.filter(
-        &df.column("t")
-            .unwrap()
-            .utf8()
-            .unwrap()
-            .into_iter()
-            .map(|x| x.unwrap_or("").len() > 0 && parse_dart_timestring_short(x.unwrap()).is_ok())
-            .collect(),
+    // rows without a valid timestamp are dropped
+    let t = df
+        .column("t")?
+        .utf8()?
+        .into_iter()
+        .map(|x| x.and_then(|x| parse_dart_timestring_short(x).ok()))
+        .collect::<Vec<Option<NaiveDateTime>>>();
+    let read = df.height();
+    let mut df = check_timestamps(
+        df.filter(&t.iter().map(|x| x.is_some()).collect())?,
+        read,
+        path,
     )?;
 
-    let s = DatetimeChunked::from_naive_datetime(
-        "t",
-        df.column("t")
-            .unwrap()
-            .utf8()
-            .unwrap()
-            .into_iter()
-            .map(|x| parse_dart_timestring_short(x.unwrap()).unwrap()),
-        TimeUnit::Milliseconds,
-    );
+    let s =
+        DatetimeChunked::from_naive_datetime("t", t.into_iter().flatten(), TimeUnit::Milliseconds);
 
-    df.replace_or_add("t", s).cloned()
+    Ok(df.replace_or_add("t", s).cloned()?)
 }
 
-pub fn read_raw_csv(path: &PathBuf) -> Result<DataFrame, PolarsError> {
-    let schema = Some(generate_flextail_schema(get_num_of_sensors_from_file(
-        &path,
-    )));
-    let reader = CsvReader::from_path(path).unwrap().with_ignore_errors(true);
+pub fn read_raw_csv(path: &PathBuf) -> DataResult<DataFrame> {
+    read_raw_csv_with(path, &CsvReadOptions::default())
+}
 
-    let reader = match schema {
-        Some(schema) => reader.with_schema(Arc::new(schema)),
-        None => reader.infer_schema(Some(100)),
-    };
+pub fn read_raw_csv_with(path: &PathBuf, options: &CsvReadOptions) -> DataResult<DataFrame> {
+    Ok(scan_raw_csv_with(path, options)?.collect()?)
+}
 
-    convert_i64_to_time(
-        reader
-            .has_header(false)
-            .finish()
-            .as_mut()
-            .map_err(|_| PolarsError::NoData("cannot get as mut".into()))?,
-        None,
+pub fn scan_raw_csv(path: &PathBuf) -> DataResult<LazyFrame> {
+    scan_raw_csv_with(path, &CsvReadOptions::default())
+}
+
+/// Like [`read_raw_csv_with`] without reading the file yet. Rows without a valid time are
+/// dropped. Filters on `t` are applied after converting the time column, use
+/// [`scan_raw_csv_between_with`] to filter while scanning.
+pub fn scan_raw_csv_with(path: &PathBuf, options: &CsvReadOptions) -> DataResult<LazyFrame> {
+    scan_raw_csv_in_range(path, options, None)
+}
+
+pub fn scan_raw_csv_between(
+    path: &PathBuf,
+    begin: NaiveDateTime,
+    end: NaiveDateTime,
+) -> DataResult<LazyFrame> {
+    scan_raw_csv_between_with(path, &CsvReadOptions::default(), begin, end)
+}
+
+/// Only the rows from `begin` to `end` (inclusive, UTC), e.g. a single day of a long
+/// session. For files with millisecond times the range is checked while scanning, so the
+/// other rows are never materialized.
+pub fn scan_raw_csv_between_with(
+    path: &PathBuf,
+    options: &CsvReadOptions,
+    begin: NaiveDateTime,
+    end: NaiveDateTime,
+) -> DataResult<LazyFrame> {
+    scan_raw_csv_in_range(
+        path,
+        options,
+        Some((begin.timestamp_millis(), end.timestamp_millis())),
     )
 }
 
-fn read_csv_file(file: &PathBuf, output_type: OutputType) -> PolarsResult<DataFrame> {
+fn scan_raw_csv_in_range(
+    path: &PathBuf,
+    options: &CsvReadOptions,
+    range: Option<(i64, i64)>,
+) -> DataResult<LazyFrame> {
+    // checked first, counting the sensors expects an existing file
+    fs::metadata(path)?;
+    let iso_time = has_iso_time(path, &OutputType::raw, options.delimiter);
+    let mut schema =
+        generate_flextail_schema(get_num_of_sensors_from_file_with(&path, options.delimiter));
+    if iso_time {
+        schema.with_column("t".into(), DataType::Utf8);
+    }
+    let in_range = |t: Expr| match range {
+        Some((begin, end)) => t.clone().gt_eq(lit(begin)).and(t.lt_eq(lit(end))),
+        None => lit(true),
+    };
+
+    let lf = LazyCsvReader::new(path)
+        .with_schema(Arc::new(schema))
+        .with_delimiter(options.delimiter)
+        .with_ignore_errors(true)
+        .has_header(false)
+        .finish()?;
+    Ok(match iso_time {
+        // the strings can only be compared after parsing
+        true => lf.with_column(iso_time_expr(&options.timezone)).filter(
+            col("t")
+                .is_not_null()
+                .and(in_range(col("t").cast(DataType::Int64))),
+        ),
+        // filtered before `t` is replaced, so the predicate is pushed down into the scan
+        false => lf
+            .filter(col("t").gt(lit(0i64)).and(in_range(col("t"))))
+            .with_column(col("t").cast(DataType::Datetime(
+                TimeUnit::Milliseconds,
+                Some(options.timezone.clone()),
+            ))),
+    })
+}
+
+fn read_csv_file(file: &PathBuf, output_type: OutputType) -> DataResult<DataFrame> {
+    read_csv_file_with(file, output_type, &CsvReadOptions::default())
+}
+
+fn read_csv_file_with(
+    file: &PathBuf,
+    output_type: OutputType,
+    options: &CsvReadOptions,
+) -> DataResult<DataFrame> {
     (match output_type {
-        OutputType::points => read_points_csv,
-        OutputType::raw => read_raw_csv,
-        OutputType::logs => read_logs_csv,
-    })(file)
+        OutputType::points => read_points_csv_with,
+        OutputType::raw => read_raw_csv_with,
+        OutputType::logs => read_logs_csv_with,
+    })(file, options)
 }
 
 pub fn df_column_to_data_point(
@@ -428,53 +1074,1002 @@ pub fn df_column_to_data_point(
     )
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum SusLevel {
     Ok,
     Sus(String),
     TurboSus(String),
 }
 
-fn validate_rows(df: DataFrame) -> SusLevel {
-    let n = (df.shape().1 - 8) / 2;
-    let mut sus_counter: usize = 0;
+/// A sensor column which reads the same value for at least
+/// [`StuckSensorDetection::min_rows`] consecutive rows, `value` 0 is a flatline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuckSensor {
+    pub column: String,
+    pub value: i32,
+    /// length of the longest run of `value`
+    pub rows: usize,
+}
 
-    for i in 0..df.shape().0 {
-        if any_value_to_i16(
-            df.get_row(i)
-                .unwrap()
-                .0
-                .iter()
-                .take(2 * n)
-                .collect::<Vec<&AnyValue<'_>>>(),
-        )
-        .into_iter()
-        .filter(|x| x.abs() > 500)
-        .count()
-            > 2
-        {
-            sus_counter += 1;
+/// Parameters for [`find_stuck_sensors`].
+#[derive(Debug, Clone)]
+pub struct StuckSensorDetection {
+    /// healthy sensors are noisy, so a run of this many equal values is suspicious
+    pub min_rows: usize,
+}
+
+impl Default for StuckSensorDetection {
+    fn default() -> Self {
+        StuckSensorDetection { min_rows: 500 }
+    }
+}
+
+/// Thresholds for validating the sensor values of a raw frame, the defaults are the ones
+/// used by [`validate_file`].
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    /// sensor values with a larger absolute value are outliers
+    pub sensor_limit: i32,
+    /// rows with more outliers than this are faulty
+    pub max_outliers: usize,
+    /// share of faulty rows above which a frame is `Sus`
+    pub sus_share: f32,
+    /// share of faulty rows above which a frame is `TurboSus`
+    pub turbo_sus_share: f32,
+    pub stuck_sensors: StuckSensorDetection,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            sensor_limit: 500,
+            max_outliers: 2,
+            sus_share: 0.01,
+            turbo_sus_share: 0.02,
+            stuck_sensors: StuckSensorDetection::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub level: SusLevel,
+    pub stuck_sensors: Vec<StuckSensor>,
+}
+
+pub(crate) fn is_sensor_column(name: &str) -> bool {
+    (name.starts_with('l') || name.starts_with('r')) && name[1..].parse::<usize>().is_ok()
+}
+
+/// checks the sensor columns (`l1`..`ln`, `r1`..`rn`) of a flat raw frame
+pub fn find_stuck_sensors(df: &DataFrame, params: &StuckSensorDetection) -> Vec<StuckSensor> {
+    df.get_columns()
+        .iter()
+        .filter(|x| is_sensor_column(x.name()))
+        .filter_map(|x| {
+            let values = x.cast(&DataType::Int32).ok()?;
+            let mut longest: Option<(i32, usize)> = None;
+            let mut run: Option<(i32, usize)> = None;
+            for v in values.i32().ok()?.into_iter() {
+                run = match (v, run) {
+                    (Some(v), Some((last, n))) if v == last => Some((v, n + 1)),
+                    (Some(v), _) => Some((v, 1)),
+                    (None, _) => None,
+                };
+                if run.map(|r| r.1) > longest.map(|l| l.1) {
+                    longest = run;
+                }
+            }
+            longest
+                .filter(|x| x.1 >= params.min_rows)
+                .map(|(value, rows)| StuckSensor {
+                    column: x.name().to_string(),
+                    value,
+                    rows,
+                })
+        })
+        .collect()
+}
+
+/// grades a flat raw frame by the share of rows with too many outlier sensor values
+fn validate_rows(df: &DataFrame, config: &ValidationConfig) -> SusLevel {
+    let mut outliers = vec![0usize; df.height()];
+    for values in df
+        .get_columns()
+        .iter()
+        .filter(|x| is_sensor_column(x.name()))
+        .filter_map(|x| x.cast(&DataType::Int32).ok())
+    {
+        if let Ok(values) = values.i32() {
+            outliers
+                .iter_mut()
+                .zip(values.into_iter())
+                .filter(|(_, v)| v.map_or(false, |v| v.abs() > config.sensor_limit))
+                .for_each(|(n, _)| *n += 1);
         }
     }
 
+    let sus_counter = outliers
+        .into_iter()
+        .filter(|x| *x > config.max_outliers)
+        .count();
     let sus_percent = sus_counter as f32 / df.shape().0 as f32;
-    if sus_percent > 0.02 {
+    if sus_percent > config.turbo_sus_share {
         SusLevel::TurboSus(format!("{}% faulty rows", (100.0 * sus_percent).round()))
-    } else if sus_percent > 0.01 {
+    } else if sus_percent > config.sus_share {
         SusLevel::Sus(format!("{}% faulty rows", (100.0 * sus_percent).round()))
     } else {
         SusLevel::Ok
     }
 }
 
+/// [`validate_rows`] and [`find_stuck_sensors`] of a flat raw frame
+pub(crate) fn validate_flat(df: &DataFrame, config: &ValidationConfig) -> ValidationReport {
+    if df.is_empty() {
+        return ValidationReport {
+            level: SusLevel::TurboSus("empty".to_string()),
+            stuck_sensors: vec![],
+        };
+    }
+    ValidationReport {
+        level: validate_rows(df, config),
+        stuck_sensors: find_stuck_sensors(df, &config.stuck_sensors),
+    }
+}
+
 pub fn validate_file(path: &PathBuf) -> SusLevel {
     match read_raw_csv(path) {
         Ok(df) => {
             if df.is_empty() {
                 return SusLevel::TurboSus("empty".to_string());
             } else {
-                validate_rows(df)
+                validate_rows(&df, &ValidationConfig::default())
             }
         }
         _ => SusLevel::TurboSus("could not be parsed".to_string()),
     }
 }
+
+/// Grades a points file like [`validate_file`] by the share of rows with a score outside
+/// of `0..=100` or a timestamp which is not after the previous one.
+pub fn validate_points_file(path: &PathBuf) -> SusLevel {
+    let df = match read_points_csv(path) {
+        Ok(df) if df.is_empty() => return SusLevel::TurboSus("empty".to_string()),
+        Ok(df) => df,
+        _ => return SusLevel::TurboSus("could not be parsed".to_string()),
+    };
+    let (score, t) = match (df["score"].f64(), df["t"].datetime()) {
+        (Ok(score), Ok(t)) => (score, t),
+        _ => return SusLevel::TurboSus("could not be parsed".to_string()),
+    };
+
+    let out_of_range = score
+        .into_iter()
+        .flatten()
+        .filter(|x| !(0.0..=100.0).contains(x))
+        .count();
+    let t = t.into_iter().flatten().collect::<Vec<i64>>();
+    let unordered = t.windows(2).filter(|x| x[1] <= x[0]).count();
+
+    let rows = df.height() as f32;
+    let (range_percent, order_percent) = (out_of_range as f32 / rows, unordered as f32 / rows);
+    let message = format!(
+        "{}% scores out of range, {}% unordered or duplicate timestamps",
+        (100.0 * range_percent).round(),
+        (100.0 * order_percent).round()
+    );
+    let sus_percent = range_percent.max(order_percent);
+    if sus_percent > 0.02 {
+        SusLevel::TurboSus(message)
+    } else if sus_percent > 0.01 {
+        SusLevel::Sus(message)
+    } else {
+        SusLevel::Ok
+    }
+}
+
+/// [`validate_file`] together with the sensors that are stuck at a value
+pub fn validate_file_report(path: &PathBuf, params: &StuckSensorDetection) -> ValidationReport {
+    match read_raw_csv(path) {
+        Ok(df) => validate_flat(
+            &df,
+            &ValidationConfig {
+                stuck_sensors: params.clone(),
+                ..Default::default()
+            },
+        ),
+        Err(_) => ValidationReport {
+            level: SusLevel::TurboSus("could not be parsed".to_string()),
+            stuck_sensors: vec![],
+        },
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, io::Write, path::PathBuf, str::FromStr};
+
+    use chrono::NaiveDateTime;
+    use flate2::{write::GzEncoder, Compression};
+    use polars::prelude::*;
+    use uuid::Uuid;
+
+    use super::{
+        convert_i64_to_time_tz, create_user_df, create_user_df_from_files,
+        create_user_df_from_files_with, melt_sensor_lists, read_arrow_file, read_bytes_into_df,
+        read_input_file_into_df, read_input_file_into_df_with, read_logs_csv, read_parquet_file,
+        read_parquet_glob, read_points_csv, read_points_csv_with, read_raw_csv, read_raw_csv_with,
+        remove_temp_file, scan_raw_csv, scan_raw_csv_between, validate_file_report,
+        validate_points_file, write_df, write_df_with_options, write_flat_df, write_ndjson,
+        ConcatOptions, CsvReadOptions, ParquetWriteOptions, StuckSensorDetection, SusLevel,
+        TableFormat, WriteOptions, DEFAULT_TIMEZONE,
+    };
+    use crate::{
+        df::raw::tests::fixture,
+        error::DataError,
+        misc::{get_num_of_sensors_from_file, get_num_of_sensors_from_file_with},
+        schema::OutputType,
+    };
+
+    pub(crate) fn temp_file(content: &str, ending: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}{}", Uuid::new_v4(), ending));
+        fs::write(&path, content).expect("could not write temp file");
+        path
+    }
+
+    #[test]
+    fn read_parquet_bytes() {
+        let mut df = df!["t" => [1i64, 2, 3], "score" => [0.5, 0.7, 0.9]].unwrap();
+        let mut bytes: Vec<u8> = vec![];
+        ParquetWriter::new(&mut bytes).finish(&mut df).unwrap();
+
+        assert_eq!(TableFormat::from_magic_bytes(&bytes), TableFormat::Parquet);
+        assert!(read_bytes_into_df(&bytes, None).unwrap().frame_equal(&df));
+        assert!(read_bytes_into_df(&bytes, Some(TableFormat::Parquet))
+            .unwrap()
+            .frame_equal(&df));
+    }
+
+    #[test]
+    fn skip_files_of_other_type() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut raw = root.clone();
+        raw.push("raw");
+        fs::create_dir_all(&raw).unwrap();
+
+        let sensors = vec!["1000"; 18].join(",");
+        fs::write(
+            raw.join("1681294830000-1681294831000.csv"),
+            format!("{},0,0,1000,0,0,0,4000,1681294830000\n", sensors),
+        )
+        .unwrap();
+        fs::write(
+            raw.join("1681294840000-1681294841000.csv"),
+            "1681294840000,80.0,70.0,90.0,office\n",
+        )
+        .unwrap();
+
+        let df = create_user_df(&vec![root.clone()], OutputType::raw, None).unwrap();
+        fs::remove_dir_all(root).unwrap();
+        assert_eq!(df.height(), 1);
+    }
+
+    #[test]
+    fn align_raw_files_with_different_sensor_counts() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut raw = root.clone();
+        raw.push("raw");
+        fs::create_dir_all(&raw).unwrap();
+
+        for (n, t) in [
+            (18, 1681294820000i64),
+            (9, 1681294830000),
+            (18, 1681294840000),
+        ] {
+            let sensors = vec!["1000"; 2 * n].join(",");
+            fs::write(
+                raw.join(format!("{}-{}.csv", t, t + 1000)),
+                format!("{},0,0,1000,0,0,0,4000,{}\n", sensors, t),
+            )
+            .unwrap();
+        }
+
+        let df = create_user_df(&vec![root.clone()], OutputType::raw, None).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(df.height(), 3);
+        assert_eq!(df.width(), 2 * 18 + 8);
+        assert_eq!(df["l10"].null_count(), 1);
+        assert!(df["t"].series_equal(&df["t"].sort(false)));
+        assert_eq!(
+            df["l10"]
+                .is_null()
+                .into_iter()
+                .collect::<Vec<Option<bool>>>(),
+            vec![Some(false), Some(true), Some(false)]
+        );
+    }
+
+    #[test]
+    fn melt_acc_column() {
+        let df = fixture(vec![vec![1, 2, 3], vec![4, 5, 6]], 9, 20);
+        let melted = melt_sensor_lists(&df, &["acc"]).unwrap();
+
+        assert_eq!(melted.height(), 6);
+        assert_eq!(
+            melted["index"]
+                .u32()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<u32>>(),
+            vec![0, 1, 2, 0, 1, 2]
+        );
+        assert_eq!(
+            melted["value"]
+                .i32()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<i32>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn write_csv_with_header() {
+        let path = temp_file("", ".csv");
+        let mut df = df!["t" => [1i64, 2], "score" => [0.5, 0.7]].unwrap();
+        write_df_with_options(
+            &path,
+            &mut df,
+            &WriteOptions {
+                csv_header: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let read = CsvReader::from_path(&path)
+            .unwrap()
+            .has_header(true)
+            .finish()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(read.get_column_names(), vec!["t", "score"]);
+        assert_eq!(read.height(), 2);
+    }
+
+    #[test]
+    fn write_into_missing_directory_fails() {
+        let mut df = df!["t" => [1i64, 2], "score" => [0.5, 0.7]].unwrap();
+        let mut path = std::env::temp_dir();
+        path.push(Uuid::new_v4().to_string());
+        path.push("points.parquet");
+
+        assert!(matches!(write_df(&path, &mut df), Err(DataError::Io(_))));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unknown_ending_leaves_no_file() {
+        let mut df = df!["t" => [1i64, 2], "score" => [0.5, 0.7]].unwrap();
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}.xlsx", Uuid::new_v4()));
+
+        assert!(matches!(
+            write_df(&path, &mut df),
+            Err(DataError::UnknownFormat(_))
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn write_flat_csv() {
+        let path = temp_file("", ".csv");
+        let df = fixture(vec![vec![1, 2, 3], vec![4, 5, 6]], 9, 20);
+        write_flat_df(
+            &path,
+            df.0,
+            9,
+            &WriteOptions {
+                csv_header: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let read = CsvReader::from_path(&path)
+            .unwrap()
+            .has_header(true)
+            .finish()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        // v, t, 9 left, 9 right, 3 acc, 3 gyro
+        assert_eq!(read.width(), 26);
+        assert_eq!(read.height(), 2);
+    }
+
+    #[test]
+    fn zstd_parquet_is_smaller_than_uncompressed() {
+        let df = fixture(vec![vec![0, 0, 1000]; 5000], 18, 20);
+        let write = |compression| {
+            let path = temp_file("", ".parquet");
+            let options = WriteOptions {
+                parquet: ParquetWriteOptions {
+                    compression: Some(compression),
+                    row_group_size: Some(1000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            write_df_with_options(&path, &mut df.0.clone(), &options).unwrap();
+            let size = fs::metadata(&path).unwrap().len();
+            assert_eq!(read_parquet_file(&path).unwrap().height(), 5000);
+            fs::remove_file(path).unwrap();
+            size
+        };
+
+        assert!(write(ParquetCompression::Zstd(None)) < write(ParquetCompression::Uncompressed));
+    }
+
+    #[test]
+    fn convert_time_with_timezone() {
+        let mut df = df!["t" => [1681294830000i64]].unwrap();
+
+        let utc = convert_i64_to_time_tz(&mut df, None, Some("UTC")).unwrap();
+        let default = convert_i64_to_time_tz(&mut df, None, None).unwrap();
+
+        assert_eq!(
+            utc["t"].dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, Some("UTC".into()))
+        );
+        assert_eq!(
+            default["t"].dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, Some(DEFAULT_TIMEZONE.into()))
+        );
+    }
+
+    #[test]
+    fn arrow_round_trip() {
+        let mut raw = fixture(vec![vec![0, 0, 1000]; 5], 9, 20).0;
+        let mut points = df![
+            "t" => [1_681_294_830_000i64, 1_681_294_831_000],
+            "score" => [80.0, 70.0],
+        ]
+        .unwrap();
+
+        let with_ending = temp_file("", ".arrow");
+        let without_ending = temp_file("", "");
+        IpcWriter::new(fs::File::create(&with_ending).unwrap())
+            .finish(&mut raw)
+            .unwrap();
+        IpcWriter::new(fs::File::create(&without_ending).unwrap())
+            .finish(&mut points)
+            .unwrap();
+        let read_raw = read_input_file_into_df(with_ending.clone());
+        let read_points = read_input_file_into_df(without_ending.clone());
+        fs::remove_file(with_ending).unwrap();
+        fs::remove_file(without_ending).unwrap();
+
+        assert!(read_raw.unwrap().frame_equal(&raw));
+        let read_points = read_points.unwrap();
+        assert_eq!(read_points.height(), 2);
+        assert!(matches!(read_points["t"].dtype(), DataType::Datetime(_, _)));
+    }
+
+    #[test]
+    fn csv_files_sorted_by_time() {
+        let t0 = 1_681_294_830_000i64;
+        let later = temp_file(&format!("{},80.0,70.0,10.0,office\n", t0 + 1000), ".csv");
+        let earlier = temp_file(&format!("{},60.0,70.0,10.0,office\n", t0), ".csv");
+
+        let df = create_user_df_from_files(
+            vec![later.clone(), earlier.clone()],
+            OutputType::points,
+            None,
+        )
+        .unwrap();
+        fs::remove_file(later).unwrap();
+        fs::remove_file(earlier).unwrap();
+
+        assert_eq!(
+            df["t"]
+                .datetime()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<i64>>(),
+            vec![t0, t0 + 1000]
+        );
+    }
+
+    #[test]
+    fn mixed_csv_and_parquet_raw_files() {
+        let sensors = vec!["1000"; 18].join(",");
+        let lines = |t: &[i64]| {
+            t.iter()
+                .map(|t| format!("{},0,0,1000,0,0,0,4000,{}", sensors, t))
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let t0 = 1_681_294_830_000i64;
+        let csv = temp_file(&lines(&[t0 + 2000, t0 + 3000]), ".csv");
+        let converted = temp_file(&lines(&[t0, t0 + 1000]), ".csv");
+        let parquet = temp_file("", ".parquet");
+        let mut df = read_raw_csv(&converted).unwrap();
+        ParquetWriter::new(fs::File::create(&parquet).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        fs::remove_file(converted).unwrap();
+
+        let (df, _) = create_user_df_from_files_with(
+            vec![csv.clone(), parquet.clone()],
+            OutputType::raw,
+            None,
+            &ConcatOptions::default(),
+        )
+        .unwrap();
+        fs::remove_file(csv).unwrap();
+        fs::remove_file(parquet).unwrap();
+
+        assert_eq!(df.height(), 4);
+        assert_eq!(
+            df["t"]
+                .datetime()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<i64>>(),
+            vec![t0, t0 + 1000, t0 + 2000, t0 + 3000]
+        );
+    }
+
+    #[test]
+    fn keep_or_remove_temp_file() {
+        let rows = "1681294830000,80.0,70.0,10.0,office\n1681294831000,81.0,71.0,10.0,office\n";
+        let file = temp_file(rows, ".csv");
+
+        let (df, kept) = create_user_df_from_files_with(
+            vec![file.clone()],
+            OutputType::points,
+            None,
+            &ConcatOptions {
+                keep_temp: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(df.height(), 2);
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].exists());
+
+        remove_temp_file(&kept[0]);
+        assert!(!kept[0].exists());
+        // already removed, ignored
+        remove_temp_file(&kept[0]);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn unreadable_input_file_is_io_error() {
+        let missing = std::env::temp_dir().join(format!("{}.csv", Uuid::new_v4()));
+
+        // logs aren't checked for their type, so the file is only opened while concatenating
+        let result = create_user_df_from_files_with(
+            vec![missing],
+            OutputType::logs,
+            None,
+            &ConcatOptions::default(),
+        );
+
+        assert!(matches!(result, Err(PolarsError::Io(_))));
+    }
+
+    #[test]
+    fn points_file_with_out_of_range_scores() {
+        let lines = |scores: &[f64]| {
+            scores
+                .iter()
+                .enumerate()
+                .map(|(i, score)| {
+                    format!(
+                        "{},{},70.0,10.0,office",
+                        1_681_294_830_000i64 + i as i64 * 1000,
+                        score
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let mut scores = vec![80.0; 20];
+        let valid = temp_file(&lines(&scores), ".csv");
+        scores[3] = 250.0;
+        let invalid = temp_file(&lines(&scores), ".csv");
+
+        let valid_level = validate_points_file(&valid);
+        let invalid_level = validate_points_file(&invalid);
+        fs::remove_file(valid).unwrap();
+        fs::remove_file(invalid).unwrap();
+
+        assert_eq!(valid_level, SusLevel::Ok);
+        assert!(matches!(invalid_level, SusLevel::TurboSus(_)));
+    }
+
+    #[test]
+    fn report_stuck_sensor() {
+        let lines = (0..600)
+            .map(|i| {
+                let left = (0..9)
+                    .map(|k| if k == 2 { 123 } else { (i * 7 + k) % 50 })
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>();
+                let right = (0..9).map(|k| ((i * 3 + k) % 40).to_string());
+                format!(
+                    "{},{},0,0,1000,0,0,0,4000,{}",
+                    left.join(","),
+                    right.collect::<Vec<String>>().join(","),
+                    1_681_294_830_000i64 + i as i64 * 20
+                )
+            })
+            .collect::<Vec<String>>();
+        let path = temp_file(&lines.join("\n"), ".csv");
+
+        let report = validate_file_report(&path, &StuckSensorDetection::default());
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(report.stuck_sensors.len(), 1);
+        assert_eq!(report.stuck_sensors[0].column, "l3");
+        assert_eq!(report.stuck_sensors[0].value, 123);
+        assert_eq!(report.stuck_sensors[0].rows, 600);
+    }
+
+    #[test]
+    fn ndjson_line_per_row() {
+        let n = 25_000;
+        let df = df![
+            "t" => (0..n as i64).map(|x| 1_681_294_830_000 + x).collect::<Vec<i64>>(),
+            "score" => vec![80.0; n],
+        ]
+        .unwrap();
+
+        let mut buf: Vec<u8> = vec![];
+        write_ndjson(&df, &mut buf).unwrap();
+        let lines = String::from_utf8(buf).unwrap();
+
+        assert_eq!(lines.lines().count(), n);
+        assert!(lines.lines().last().unwrap().contains("1681294854999"));
+    }
+
+    #[test]
+    fn scan_raw_csv_with_time_filter() {
+        // every 12 hours over three days
+        let t0 = 1_681_294_830_000i64;
+        let hours = 3_600_000;
+        let sensors = vec!["1000"; 18].join(",");
+        let path = temp_file(
+            &(0..6)
+                .map(|i| format!("{},0,0,1000,0,0,0,4000,{}\n", sensors, t0 + i * 12 * hours))
+                .collect::<String>(),
+            ".csv",
+        );
+        let end = t0 + 24 * hours - 1;
+
+        let eager = read_raw_csv(&path).unwrap();
+        let first_day = scan_raw_csv_between(
+            &path,
+            NaiveDateTime::from_timestamp_millis(t0).unwrap(),
+            NaiveDateTime::from_timestamp_millis(end).unwrap(),
+        )
+        .unwrap();
+        let plan = first_day.describe_optimized_plan().unwrap();
+        let first_day = first_day.collect().unwrap();
+        fs::remove_file(path).unwrap();
+
+        // the range is part of the selection of the scan, not a filter on top of it
+        let scan = &plan[plan.find("CSV SCAN").unwrap()..];
+        assert!(scan.contains("SELECTION"));
+        assert!(scan.contains(&end.to_string()));
+        assert_eq!(eager.height(), 6);
+        assert_eq!(first_day.height(), 2);
+        assert_eq!(first_day.schema(), eager.schema());
+    }
+
+    #[test]
+    fn scan_raw_csv_with_iso_offsets() {
+        let sensors = vec!["1000"; 18].join(",");
+        let path = temp_file(
+            &[
+                "2023-04-12T10:20:30.000Z",
+                "2023-04-12T12:20:31.000+02:00",
+                "2023-04-12T05:20:32-05:00",
+            ]
+            .map(|t| format!("{},0,0,1000,0,0,0,4000,{}\n", sensors, t))
+            .concat(),
+            ".csv",
+        );
+
+        let df = scan_raw_csv(&path).unwrap().collect().unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            df["t"]
+                .datetime()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<Option<i64>>>(),
+            vec![
+                Some(1_681_294_830_000),
+                Some(1_681_294_831_000),
+                Some(1_681_294_832_000)
+            ]
+        );
+    }
+
+    #[test]
+    fn semicolon_points_with_header_and_iso_time() {
+        let path = temp_file(
+            "t;score;posture;movement;activity\n\
+             2023-04-12T10:20:30.000Z;80.0;70.0;10.0;office\n\
+             2023-04-12T12:20:31+02:00;82.0;72.0;10.0;office\n",
+            ".csv",
+        );
+        let options = ConcatOptions {
+            csv: CsvReadOptions {
+                delimiter: b';',
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let read = read_input_file_into_df_with(path.clone(), &options.csv).unwrap();
+        let (concatenated, _) =
+            create_user_df_from_files_with(vec![path.clone()], OutputType::points, None, &options)
+                .unwrap();
+        fs::remove_file(path).unwrap();
+
+        for df in [read, concatenated] {
+            assert_eq!(df.height(), 2);
+            assert_eq!(
+                df["t"]
+                    .datetime()
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Vec<Option<i64>>>(),
+                vec![Some(1_681_294_830_000), Some(1_681_294_831_000)]
+            );
+        }
+    }
+
+    #[test]
+    fn read_semicolon_delimited_raw_csv() {
+        let sensors = vec!["1000"; 18].join(";");
+        let path = temp_file(
+            &[1_681_294_830_000i64, 1_681_294_831_000]
+                .map(|t| format!("{};0;0;1000;0;0;0;4000;{}\n", sensors, t))
+                .concat(),
+            ".csv",
+        );
+        let options = CsvReadOptions {
+            delimiter: b';',
+            ..Default::default()
+        };
+
+        assert_eq!(get_num_of_sensors_from_file_with(&path, b';'), 9);
+        assert_eq!(get_num_of_sensors_from_file(&path), 0);
+        let df = read_raw_csv_with(&path, &options).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert_eq!(df.width(), 26);
+    }
+
+    #[test]
+    fn read_logs_csv_skips_invalid_timestamps() {
+        let path = temp_file(
+            "2023-04-12 10:20:30.000, Ble, INFO, connected\n\
+             not a time, Ble, INFO, garbage\n\
+             , Ble, INFO, empty\n\
+             2023-04-12 10:20:31.500, App, SEVERE, crash\n",
+            ".csv",
+        );
+        let df = read_logs_csv(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert_eq!(df["t"].datetime().unwrap().get(1), Some(1_681_294_831_500));
+    }
+
+    #[test]
+    fn logs_with_delimiter_in_message() {
+        // five fields when counting commas, like a points file
+        let path = temp_file(
+            "2023-04-12 10:20:30.000,BLE,INFO,\"connected, mtu 247\"\n\
+             2023-04-12 10:20:31.000,BLE,INFO,\"rssi -60, -62, -61\"\n",
+            ".csv",
+        );
+
+        let df = create_user_df_from_files(vec![path.clone()], OutputType::logs, None).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(df.height(), 2);
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}.csv", Uuid::new_v4()));
+
+        assert!(matches!(read_raw_csv(&path), Err(DataError::Io(_))));
+        assert!(matches!(read_points_csv(&path), Err(DataError::Io(_))));
+        assert!(matches!(
+            read_input_file_into_df(path),
+            Err(DataError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn bad_header_and_timestamps_are_parse_errors() {
+        let header = temp_file("time,score\n1681294830000,80.0\n", ".csv");
+        let points = temp_file("not-a-time,80.0,70.0,10.0,office\n", ".csv");
+        let logs = temp_file("yesterday,BLE,INFO,connected\n", ".csv");
+
+        assert!(matches!(read_points_csv(&header), Err(DataError::Parse(_))));
+        assert!(matches!(read_points_csv(&points), Err(DataError::Parse(_))));
+        assert!(matches!(read_logs_csv(&logs), Err(DataError::Parse(_))));
+
+        for path in [header, points, logs] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn read_two_parquet_shards() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        fs::create_dir_all(&root).unwrap();
+        let pattern = format!("{}/*.parquet", root.to_str().unwrap());
+        let mut late = df!["t" => [3i64, 4, 5], "score" => [0.3, 0.4, 0.5]].unwrap();
+        let mut early = df!["t" => [1i64, 2], "score" => [0.1, 0.2]].unwrap();
+        write_df(&root.join("a.parquet"), &mut late).unwrap();
+        write_df(&root.join("b.parquet"), &mut early).unwrap();
+
+        let df = read_parquet_glob(&pattern).unwrap();
+        assert_eq!(df.height(), late.height() + early.height());
+        assert_eq!(df["t"].i64().unwrap().get(0), Some(1));
+
+        let mut other = df!["t" => [6i64], "posture" => [0.6]].unwrap();
+        write_df(&root.join("c.parquet"), &mut other).unwrap();
+        assert!(matches!(
+            read_parquet_glob(&pattern),
+            Err(DataError::Polars(PolarsError::SchemaMismatch(_)))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+
+        let empty = read_parquet_glob(&pattern).unwrap();
+        assert_eq!(empty.height(), 0);
+        assert_eq!(
+            empty.get_column_names(),
+            vec!["t", "score", "posture", "movement", "activity"]
+        );
+    }
+
+    #[test]
+    fn missing_table_file_is_io_error() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}.parquet", Uuid::new_v4()));
+
+        assert!(matches!(read_arrow_file(&path), Err(DataError::Io(_))));
+        let e: PolarsError = read_parquet_file(&path).unwrap_err().into();
+        assert!(matches!(e, PolarsError::Io(_)));
+    }
+
+    #[test]
+    fn read_points_csv_with_optional_header() {
+        let rows = "1681294830000,80.0,70.0,10.0,office\n1681294831000,81.0,71.0,10.0,office\n";
+        let without = temp_file(rows, ".csv");
+        let with = temp_file(
+            &format!("t,score,posture,movement,activity\n{}", rows),
+            ".csv",
+        );
+
+        let a = read_points_csv(&without).unwrap();
+        let b = read_points_csv(&with).unwrap();
+        fs::remove_file(without).unwrap();
+        fs::remove_file(with).unwrap();
+
+        assert_eq!(a.height(), 2);
+        assert!(a.frame_equal_missing(&b));
+    }
+
+    #[test]
+    fn read_gzipped_points_csv() {
+        let rows = "1681294830000,80.0,70.0,10.0,office\n1681294831000,81.0,71.0,10.0,office\n";
+        let plain = temp_file(rows, ".csv");
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(rows.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        // without an ending the format is detected from the magic bytes
+        let gzipped = temp_file("", ".csv.gz");
+        let no_ending = temp_file("", "");
+        for path in [&gzipped, &no_ending] {
+            fs::write(path, &bytes).unwrap();
+        }
+
+        let expected = read_input_file_into_df(plain.clone()).unwrap();
+        assert_eq!(
+            TableFormat::from_str(no_ending.to_str().unwrap()),
+            Ok(TableFormat::CsvGz)
+        );
+        assert_eq!(TableFormat::from_magic_bytes(&bytes), TableFormat::CsvGz);
+        assert_eq!(
+            TableFormat::from_str("points.csv.gz"),
+            Ok(TableFormat::CsvGz)
+        );
+        assert!(TableFormat::from_str("points.tar.gz").is_err());
+        assert!(read_input_file_into_df(gzipped.clone())
+            .unwrap()
+            .frame_equal_missing(&expected));
+        assert!(read_input_file_into_df(no_ending.clone())
+            .unwrap()
+            .frame_equal_missing(&expected));
+        assert_eq!(read_bytes_into_df(&bytes, None).unwrap().height(), 2);
+
+        for path in [plain, gzipped, no_ending] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn read_points_csv_with_iso_time() {
+        let path = temp_file(
+            "2023-04-12T10:20:30.000Z,80.0,70.0,10.0,office\n2023-04-12T10:20:31.500,81.0,71.0,10.0,office\n",
+            ".csv",
+        );
+        let df = read_points_csv(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert!(matches!(df["t"].dtype(), DataType::Datetime(_, _)));
+        assert_eq!(df["t"].datetime().unwrap().get(1), Some(1_681_294_831_500));
+    }
+
+    #[test]
+    fn iso_time_is_parsed_into_timezone() {
+        let path = temp_file(
+            "t,score,posture,movement,activity\n\
+             2023-04-12T10:20:30.000,80.0,70.0,10.0,office\n\
+             not a time,81.0,71.0,10.0,office\n\
+             2023-04-12T12:20:31+02:00,82.0,72.0,10.0,office\n\
+             2023-04-12T10:20:32.000Z,83.0,73.0,10.0,office\n",
+            ".csv",
+        );
+        let df = read_points_csv_with(
+            &path,
+            &CsvReadOptions {
+                timezone: "America/New_York".into(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            df["t"].dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, Some("America/New_York".into()))
+        );
+        assert_eq!(
+            df["t"]
+                .datetime()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<Option<i64>>>(),
+            vec![
+                Some(1_681_294_830_000),
+                Some(1_681_294_831_000),
+                Some(1_681_294_832_000)
+            ]
+        );
+    }
+}
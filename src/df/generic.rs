@@ -1,15 +1,40 @@
 use polars::prelude::*;
 
 #[derive(Debug, derive_more::Deref)]
-pub struct GenericTimeBoundDf(DataFrame);
+pub struct GenericTimeBoundDf {
+    #[deref]
+    df: DataFrame,
+    time_column: String,
+}
 
 impl GenericTimeBoundDf {
     pub fn new(df: DataFrame) -> GenericTimeBoundDf {
-        GenericTimeBoundDf(df)
+        GenericTimeBoundDf {
+            df,
+            time_column: "t".into(),
+        }
+    }
+
+    /// for frames whose datetime column is not called `t`, e.g. `timestamp` or `ts`
+    pub fn with_time_column(df: DataFrame, time_column: &str) -> PolarsResult<GenericTimeBoundDf> {
+        if df.get_column_names().into_iter().any(|x| x == time_column) {
+            Ok(GenericTimeBoundDf {
+                df,
+                time_column: time_column.into(),
+            })
+        } else {
+            Err(PolarsError::SchemaMismatch(
+                format!("df has no {} column", time_column).into(),
+            ))
+        }
+    }
+
+    pub fn time_column(&self) -> &str {
+        &self.time_column
     }
 
     pub fn time(&self) -> &Logical<DatetimeType, Int64Type> {
-        self.0["t"].datetime().unwrap()
+        self.df[self.time_column.as_str()].datetime().unwrap()
     }
 }
 
@@ -17,10 +42,38 @@ impl TryFrom<DataFrame> for GenericTimeBoundDf {
     type Error = PolarsError;
 
     fn try_from(value: DataFrame) -> Result<Self, Self::Error> {
-        if value.get_column_names().into_iter().any(|x| x == "t") {
-            Ok(GenericTimeBoundDf(value))
-        } else {
-            Err(PolarsError::SchemaMismatch("df has no t column".into()))
-        }
+        GenericTimeBoundDf::with_time_column(value, "t")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use polars::prelude::*;
+
+    use super::GenericTimeBoundDf;
+    use crate::df::time_bound_df::TimeBoundDf;
+
+    #[test]
+    fn slice_by_timestamp_column() {
+        let t0 = 1_681_294_830_000i64;
+        let day = 86_400_000;
+        let mut df = df![
+            "timestamp" => [t0, t0 + 1000, t0 + day],
+            "value" => [1, 2, 3]
+        ]
+        .unwrap();
+        let timestamp = df["timestamp"]
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+            .unwrap();
+        df.with_column(timestamp).unwrap();
+        assert!(GenericTimeBoundDf::with_time_column(df.clone(), "ts").is_err());
+
+        let df = GenericTimeBoundDf::with_time_column(df, "timestamp").unwrap();
+        let first = df.day(NaiveDate::from_ymd_opt(2023, 4, 12).unwrap());
+
+        assert_eq!(first.height(), 2);
+        assert_eq!(first.time_column(), "timestamp");
+        assert_eq!(df.get_days(None).len(), 2);
     }
 }
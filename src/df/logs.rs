@@ -1,5 +1,7 @@
 use polars::prelude::*;
 
+use crate::logs::LogEntry;
+
 #[derive(Debug, derive_more::Deref)]
 pub struct LogsDf(DataFrame);
 
@@ -7,4 +9,80 @@ impl LogsDf {
     pub fn time(&self) -> &Logical<DatetimeType, Int64Type> {
         self.0["t"].datetime().unwrap()
     }
+
+    /// columns `t`, `logger`, `log_level` (categorical) and `message`
+    pub fn from_entries(entries: &[LogEntry]) -> PolarsResult<LogsDf> {
+        Ok(LogsDf(DataFrame::new(vec![
+            DatetimeChunked::from_naive_datetime(
+                "t",
+                entries.iter().map(|x| x.timestamp),
+                TimeUnit::Milliseconds,
+            )
+            .into_series(),
+            Series::new(
+                "logger",
+                entries
+                    .iter()
+                    .map(|x| x.logger.as_str())
+                    .collect::<Vec<&str>>(),
+            ),
+            Series::new(
+                "log_level",
+                entries
+                    .iter()
+                    .map(|x| format!("{:?}", x.log_level))
+                    .collect::<Vec<String>>(),
+            )
+            .cast(&DataType::Categorical(None))?,
+            Series::new(
+                "message",
+                entries
+                    .iter()
+                    .map(|x| x.message.as_str())
+                    .collect::<Vec<&str>>(),
+            ),
+        ])?))
+    }
+}
+
+impl TryFrom<Vec<LogEntry>> for LogsDf {
+    type Error = PolarsError;
+
+    fn try_from(entries: Vec<LogEntry>) -> PolarsResult<LogsDf> {
+        LogsDf::from_entries(&entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use polars::prelude::*;
+
+    use super::LogsDf;
+    use crate::logs::LogEntry;
+
+    #[test]
+    fn logs_df_from_entries() {
+        let entries = vec![
+            "2023-04-12 10:20:30.000, BLE, INFO, connected to FT1A2",
+            "2023-04-12 10:20:31.000, VibrationTrigger, INFO, vibration: 3",
+            "2023-04-12 10:20:32.000, BLE, WARNING, disconnected from FT1A2",
+        ]
+        .into_iter()
+        .map(|x| LogEntry::from_str(x).unwrap())
+        .collect::<Vec<LogEntry>>();
+
+        let df = LogsDf::try_from(entries).unwrap();
+        let counts =
+            df.0.clone()
+                .lazy()
+                .groupby([col("log_level")])
+                .agg([col("message").count()])
+                .collect()
+                .unwrap();
+
+        assert_eq!(df.height(), 3);
+        assert_eq!(counts.height(), 2);
+    }
 }
@@ -1,5 +1,6 @@
-use std::{f64::consts::PI, path::PathBuf};
+use std::{f64::consts::PI, io::Write, path::PathBuf};
 
+use chrono::{Duration, NaiveDateTime};
 use flex_rs_core::{
     case_position::CasePosition, measurement::Measurement,
     sensor_angles::calc_angles_with_default_params, FlextailPositionContainer,
@@ -7,17 +8,22 @@ use flex_rs_core::{
 use polars::{frame::row::Row, lazy::dsl::concat_list, prelude::*};
 
 use rayon::prelude::*;
+use timespan::{DatedData, TimedData};
 
 use crate::{
     clustered_data::NDHistogram,
-    misc::{get_num_of_sensors, infer_df_type, timeit},
-    schema::OutputType,
+    misc::{get_num_of_sensors, infer_df_type, is_new_schema},
+    schema::{OutputType, RawDfJS},
     series::{ToSeries, ToVec},
 };
 
 use derive_more::Deref;
 
-use super::{create_user_df, create_user_df_from_files, read_input_file_into_df, ColNameGenerator};
+use super::{
+    concat_sorted, create_user_df, create_user_df_from_files, first_list_len, flatten_df,
+    is_sensor_column, melt_sensor_lists, ndjson_string, read_input_file_into_df, validate_flat,
+    write_ndjson, ColNameGenerator, SusLevel, ValidationConfig, ValidationReport,
+};
 
 pub fn transform_to_new_schema(df: &mut DataFrame) -> PolarsResult<DataFrame> {
     if df.is_empty() || df.shape().0 == 0 || df.shape().1 <= 7 {
@@ -39,9 +45,87 @@ pub fn transform_to_new_schema(df: &mut DataFrame) -> PolarsResult<DataFrame> {
     }
 }
 
-#[derive(Debug, Deref)]
+/// casts `v` to the `Int32` of the flextail schema
+fn with_i32_voltage(mut df: DataFrame) -> PolarsResult<DataFrame> {
+    if let Ok(v) = df.column("v") {
+        if v.dtype() != &DataType::Int32 {
+            let v = v.cast(&DataType::Int32)?;
+            df.with_column(v)?;
+        }
+    }
+    Ok(df)
+}
+
+/// Parameters for the peak detection in [`RawDf::estimate_steps_with`].
+#[derive(Debug, Clone)]
+pub struct StepDetection {
+    /// how far (relative to the mean magnitude) a peak has to rise to count as a step
+    pub threshold: f64,
+    /// minimal time between two steps in ms
+    pub min_spacing_ms: i64,
+}
+
+impl Default for StepDetection {
+    fn default() -> Self {
+        StepDetection {
+            threshold: 0.1,
+            min_spacing_ms: 300,
+        }
+    }
+}
+
+/// Row a windowed value is assigned to, see [`RawDf::with_movement_score_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowAlignment {
+    /// the last row of the window, i.e. the value only depends on past samples
+    Trailing,
+    /// the middle row of the window
+    Centered,
+}
+
+#[derive(Debug, Clone, Deref)]
 pub struct RawDf(pub DataFrame);
 
+/// sum of the bend angles of the upper half of the sensors, the bend of [`RawDf::bend`] and
+/// [`RawDf::calc_posture_distribution`] for any sensor count
+fn upper_bend(alpha: &[f64]) -> f64 {
+    alpha.iter().take(alpha.len() / 2).sum()
+}
+
+/// rows of `previous` moved towards `next` by `frac`, null without a `frac` or if the
+/// lists have different lengths
+fn interpolate_lists(previous: &ListChunked, next: &ListChunked, frac: &[Option<f64>]) -> Series {
+    let values = |x: Option<Series>| -> Option<Vec<i32>> {
+        Some(
+            x?.cast(&DataType::Int32)
+                .ok()?
+                .i32()
+                .ok()?
+                .into_no_null_iter()
+                .collect(),
+        )
+    };
+    previous
+        .into_iter()
+        .zip(next.into_iter())
+        .zip(frac.iter())
+        .map(|((a, b), frac)| {
+            let (a, b, frac) = (values(a)?, values(b)?, (*frac)?);
+            if a.len() != b.len() {
+                return None;
+            }
+            Some(Series::new(
+                "",
+                a.iter()
+                    .zip(b.iter())
+                    .map(|(a, b)| (*a as f64 + (b - a) as f64 * frac).round() as i32)
+                    .collect::<Vec<i32>>(),
+            ))
+        })
+        .collect::<ListChunked>()
+        .into_series()
+}
+
 impl RawDf {
     pub fn get_measurement_idx(&self, idx: usize) -> Option<Measurement> {
         match self.0.get_row(idx) {
@@ -50,6 +134,117 @@ impl RawDf {
         }
     }
 
+    /// index of the row closest to `time`, if it is at most `max_distance` away
+    pub fn nearest_row(&self, time: NaiveDateTime, max_distance: Duration) -> Option<usize> {
+        let t = time.timestamp_millis();
+        self.time()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, x)| x.map(|x| (i, (x - t).abs())))
+            .min_by_key(|x| x.1)
+            .filter(|x| x.1 <= max_distance.num_milliseconds())
+            .map(|x| x.0)
+    }
+
+    pub fn measurement_at(
+        &self,
+        time: NaiveDateTime,
+        max_distance: Duration,
+    ) -> Option<Measurement> {
+        self.get_measurement_idx(self.nearest_row(time, max_distance)?)
+    }
+
+    /// Builds a new schema frame, e.g. from measurements decoded live from ble packets.
+    pub fn from_measurements(measurements: Vec<Measurement>) -> PolarsResult<RawDf> {
+        let list = |name: &str, values: &dyn Fn(&Measurement) -> &Vec<i16>| {
+            let mut s = ListChunked::from_iter(measurements.iter().map(|m| {
+                Some(Series::new(
+                    "",
+                    values(m).iter().map(|x| *x as i32).collect::<Vec<i32>>(),
+                ))
+            }))
+            .into_series();
+            s.rename(name);
+            s
+        };
+
+        Ok(RawDf(DataFrame::new(vec![
+            list("left", &|m| &m.left),
+            list("right", &|m| &m.right),
+            list("acc", &|m| &m.acc),
+            list("gyro", &|m| &m.gyro),
+            Series::new(
+                "v",
+                measurements
+                    .iter()
+                    .map(|m| m.v as i32)
+                    .collect::<Vec<i32>>(),
+            ),
+            Series::new("t", measurements.iter().map(|m| m.t).collect::<Vec<i64>>())
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?,
+        ])?))
+    }
+
+    /// stacks frames with the same schema, sorted by time
+    pub fn concat(frames: &[RawDf]) -> PolarsResult<RawDf> {
+        Ok(RawDf(concat_sorted(frames.iter().map(|x| &x.0))?))
+    }
+
+    pub fn head(&self, n: usize) -> RawDf {
+        RawDf(self.0.head(Some(n)))
+    }
+
+    pub fn tail(&self, n: usize) -> RawDf {
+        RawDf(self.0.tail(Some(n)))
+    }
+
+    /// `len` rows starting at `offset`, a negative offset counts from the end
+    pub fn slice(&self, offset: i64, len: usize) -> RawDf {
+        RawDf(self.0.slice(offset, len))
+    }
+
+    /// every `n`th row starting with the first, a rough preview without resampling
+    pub fn downsample_every_nth(&self, n: usize) -> RawDf {
+        let idx = IdxCa::from_vec(
+            "idx",
+            (0..self.0.height())
+                .step_by(n.max(1))
+                .map(|x| x as IdxSize)
+                .collect(),
+        );
+        RawDf(self.0.take(&idx).expect("could not take every nth row"))
+    }
+
+    /// sensors per side, from the length of the `left` lists or the `l1`..`ln` and
+    /// `r1`..`rn` columns of a flat frame
+    pub fn sensor_count(&self) -> usize {
+        first_list_len(&self.0, "left").unwrap_or_else(|| {
+            self.0
+                .get_column_names()
+                .into_iter()
+                .filter(|x| is_sensor_column(x))
+                .count()
+                / 2
+        })
+    }
+
+    /// Grades the sensor values like [`validate_file`](super::validate_file) without
+    /// writing the frame out first.
+    pub fn validate(&self, config: &ValidationConfig) -> ValidationReport {
+        let n = self.sensor_count();
+        match self
+            .0
+            .select(["left", "right"])
+            .and_then(|x| flatten_df(x, n))
+        {
+            Ok(flat) => validate_flat(&flat, config),
+            Err(_) => ValidationReport {
+                level: SusLevel::TurboSus("could not be parsed".to_string()),
+                stuck_sensors: vec![],
+            },
+        }
+    }
+
     pub fn time(&self) -> &Logical<DatetimeType, Int64Type> {
         self.0["t"].datetime().unwrap()
     }
@@ -66,23 +261,318 @@ impl RawDf {
         self.0["gyro"].list().unwrap()
     }
 
-    pub fn voltage(&self) -> &ChunkedArray<Int32Type> {
-        self.0["v"].i32().unwrap()
+    /// `v` is `Int32` in the schema, other integer widths (e.g. from parquet) are cast
+    pub fn voltage(&self) -> Int32Chunked {
+        let v = &self.0["v"];
+        match v.i32() {
+            Ok(v) => v.clone(),
+            Err(_) => v
+                .cast(&DataType::Int32)
+                .expect("could not cast v to i32")
+                .i32()
+                .unwrap()
+                .clone(),
+        }
+    }
+
+    /// Splits where consecutive `v` values differ by more than `threshold`, e.g. when the
+    /// sensor was swapped. Complements `split_into_time_chunks`.
+    pub fn split_on_voltage_jump(&self, threshold: i32) -> Vec<RawDf> {
+        let v = self.voltage().into_iter().collect::<Vec<Option<i32>>>();
+        let mut cuts = vec![0];
+        for i in 1..v.len() {
+            if let (Some(a), Some(b)) = (v[i - 1], v[i]) {
+                if (b - a).abs() > threshold {
+                    cuts.push(i);
+                }
+            }
+        }
+        cuts.push(v.len());
+        cuts.windows(2)
+            .filter(|x| x[1] > x[0])
+            .map(|x| self.slice(x[0] as i64, x[1] - x[0]))
+            .collect()
+    }
+
+    /// includes `movement`, `alpha` and `beta` if they were calculated before
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&RawDfJS::from(RawDf(self.0.clone())))
+    }
+
+    pub fn to_ndjson(&self) -> PolarsResult<String> {
+        ndjson_string(&self.0)
     }
 
+    /// streams the rows as ndjson, see [`write_ndjson`]
+    pub fn write_ndjson<W: Write>(&self, writer: W) -> PolarsResult<()> {
+        write_ndjson(&self.0, writer)
+    }
+
+    /// sum of the bend angles of the upper half of the sensors, see [`upper_bend`]
     pub fn bend(&self) -> Vec<f64> {
         self.calc_angles()
             .into_iter()
-            .map(|x| x.alpha.into_iter().take(9).sum())
+            .map(|x| upper_bend(&x.alpha))
             .collect()
     }
 
+    pub fn with_bend(&self) -> RawDf {
+        RawDf(
+            self.0
+                .clone()
+                .replace_or_add("bend", self.bend().to_series())
+                .unwrap()
+                .clone(),
+        )
+    }
+
+    /// time and bend angle per row, rows without a time or a finite angle are skipped
+    pub fn posture_timeseries(&self) -> (Vec<NaiveDateTime>, Vec<f64>) {
+        self.time()
+            .to_vec()
+            .into_iter()
+            .zip(self.bend())
+            .filter_map(|(t, bend)| t.filter(|_| bend.is_finite()).map(|t| (t, bend)))
+            .unzip()
+    }
+
+    pub fn acc_magnitude(&self) -> Vec<f64> {
+        self.acc()
+            .to_vec_unchecked()
+            .into_iter()
+            .map(|x| {
+                x.into_iter()
+                    .map(|v| (v as f64).powi(2))
+                    .sum::<f64>()
+                    .sqrt()
+            })
+            .collect()
+    }
+
+    pub fn with_acc_magnitude(&self) -> RawDf {
+        RawDf(
+            self.0
+                .clone()
+                .replace_or_add("acc_mag", self.acc_magnitude().to_series())
+                .unwrap()
+                .clone(),
+        )
+    }
+
+    /// rotational speed per row, `None` for missing or malformed (length ≠ 3) gyro lists
+    pub fn gyro_magnitude(&self) -> Vec<Option<f64>> {
+        self.gyro()
+            .into_iter()
+            .map(|x| {
+                let x = x.filter(|x| x.len() == 3)?.cast(&DataType::Float64).ok()?;
+                let squares = x
+                    .f64()
+                    .ok()?
+                    .into_iter()
+                    .map(|v| v.map(|v| v.powi(2)))
+                    .collect::<Option<Vec<f64>>>()?;
+                Some(squares.into_iter().sum::<f64>().sqrt())
+            })
+            .collect()
+    }
+
+    pub fn with_gyro_magnitude(&self) -> RawDf {
+        RawDf(
+            self.0
+                .clone()
+                .replace_or_add("gyro_mag", Series::new("gyro_mag", self.gyro_magnitude()))
+                .unwrap()
+                .clone(),
+        )
+    }
+
+    /// step count per day, summed over windows of `window_ms` which belong to the
+    /// day they start in
+    pub fn estimate_steps(&self, window_ms: i64) -> Vec<DatedData<usize>> {
+        self.estimate_steps_with(window_ms, &StepDetection::default())
+    }
+
+    pub fn estimate_steps_with(
+        &self,
+        window_ms: i64,
+        params: &StepDetection,
+    ) -> Vec<DatedData<usize>> {
+        let mut days: Vec<DatedData<usize>> = vec![];
+        for window in self.step_windows_with(window_ms, params) {
+            match days.last_mut() {
+                Some(day) if day.time == window.time.date() => day.data += window.data,
+                _ => days.push(DatedData {
+                    time: window.time.date(),
+                    data: window.data,
+                }),
+            }
+        }
+        days
+    }
+
+    /// step count per window of `window_ms`, starting at the first sample
+    pub fn step_windows(&self, window_ms: i64) -> Vec<TimedData<usize>> {
+        self.step_windows_with(window_ms, &StepDetection::default())
+    }
+
+    pub fn step_windows_with(
+        &self,
+        window_ms: i64,
+        params: &StepDetection,
+    ) -> Vec<TimedData<usize>> {
+        let mag = self.acc_magnitude();
+        let t: Vec<i64> = self.0["t"].to_vec_unchecked();
+        if mag.len() < 3 || t.len() != mag.len() || window_ms <= 0 {
+            return vec![];
+        }
+
+        let limit = mag.iter().sum::<f64>() / mag.len() as f64 * (1.0 + params.threshold);
+        let mut steps: Vec<usize> = vec![0; ((t.last().unwrap() - t[0]) / window_ms + 1) as usize];
+        let mut last_step: Option<i64> = None;
+
+        for i in 1..(mag.len() - 1) {
+            if mag[i] > limit
+                && mag[i] > mag[i - 1]
+                && mag[i] >= mag[i + 1]
+                && last_step.map_or(true, |x| t[i] - x >= params.min_spacing_ms)
+            {
+                steps[((t[i] - t[0]) / window_ms) as usize] += 1;
+                last_step = Some(t[i]);
+            }
+        }
+
+        steps
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| TimedData {
+                time: NaiveDateTime::from_timestamp_millis(t[0] + i as i64 * window_ms).unwrap(),
+                data: count,
+            })
+            .collect()
+    }
+
+    /// cumulative rotation per axis (gyro units * s), integrated with the trapezoidal
+    /// rule over the actual time deltas
+    pub fn integrate_gyro(&self) -> Vec<[f64; 3]> {
+        let gyro = self.gyro().to_vec_unchecked();
+        let t: Vec<i64> = self.0["t"].to_vec_unchecked();
+        let mut angles: Vec<[f64; 3]> = Vec::with_capacity(gyro.len());
+
+        for i in 0..gyro.len().min(t.len()) {
+            angles.push(match angles.last() {
+                Some(last) => {
+                    let dt = (t[i] - t[i - 1]) as f64 / 1000.0;
+                    let mut next = *last;
+                    for axis in 0..3 {
+                        next[axis] += (gyro[i - 1][axis] + gyro[i][axis]) as f64 / 2.0 * dt;
+                    }
+                    next
+                }
+                None => [0.0; 3],
+            });
+        }
+        angles
+    }
+
+    /// Reindexes the frame onto a regular grid of `interval_ms` starting at the first sample,
+    /// see [`DataFrame::upsample`]. The sensor, acc and gyro lists are linearly interpolated
+    /// between the neighbouring samples, all other columns come from the previous sample.
+    /// Grid points between two samples more than `max_gap_ms` apart are null.
+    pub fn resample_regular(&self, interval_ms: i64, max_gap_ms: i64) -> PolarsResult<RawDf> {
+        if interval_ms <= 0 {
+            return Err(PolarsError::ComputeError(
+                "resample interval has to be positive".into(),
+            ));
+        }
+        if self.0.height() == 0 {
+            return Ok(RawDf(self.0.clone()));
+        }
+
+        let mut samples = self.0.sort(["t"], false)?;
+        let mut sample_t = samples["t"].cast(&DataType::Int64)?;
+        sample_t.rename("sample_t");
+        samples.with_column(sample_t.clone())?;
+        sample_t.rename("t_ms");
+        samples.with_column(sample_t)?;
+
+        let mut grid_t = samples.select(["t"])?.upsample::<[String; 0]>(
+            [],
+            "t",
+            polars::prelude::Duration::parse(&format!("{}ms", interval_ms)),
+            polars::prelude::Duration::parse("0ms"),
+        )?["t"]
+            .cast(&DataType::Int64)?;
+        grid_t.rename("t_ms");
+        let grid = DataFrame::new(vec![grid_t.clone()])?;
+        let previous =
+            grid.join_asof(&samples, "t_ms", "t_ms", AsofStrategy::Backward, None, None)?;
+        let next = grid.join_asof(&samples, "t_ms", "t_ms", AsofStrategy::Forward, None, None)?;
+
+        // position between the previous and the next sample, `None` inside of gaps
+        let frac = grid_t
+            .i64()?
+            .into_iter()
+            .zip(previous["sample_t"].i64()?.into_iter())
+            .zip(next["sample_t"].i64()?.into_iter())
+            .map(|((g, a), b)| match (g, a, b) {
+                (Some(g), Some(a), Some(b)) if b - a <= max_gap_ms => Some(match b == a {
+                    true => 0.0,
+                    false => (g - a) as f64 / (b - a) as f64,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<Option<f64>>>();
+        let in_data: BooleanChunked = frac.iter().map(|x| x.is_some()).collect();
+
+        let columns = self
+            .0
+            .get_columns()
+            .iter()
+            .map(|x| {
+                let mut s = match x.name() {
+                    "t" => grid_t.cast(x.dtype())?,
+                    "left" | "right" | "acc" | "gyro" => interpolate_lists(
+                        previous.column(x.name())?.list()?,
+                        next.column(x.name())?.list()?,
+                        &frac,
+                    ),
+                    name => previous
+                        .column(name)?
+                        .zip_with(&in_data, &Series::full_null(name, grid.height(), x.dtype()))?,
+                };
+                s.rename(x.name());
+                Ok(s)
+            })
+            .collect::<PolarsResult<Vec<Series>>>()?;
+
+        Ok(RawDf(DataFrame::new(columns)?))
+    }
+
+    /// Long format with one row per sample and sensor, columns `t`, `side` (`l`/`r`),
+    /// `sensor_index` (starting at 0) and `value`. Rows with fewer sensors just add fewer rows.
+    pub fn to_long(&self) -> PolarsResult<DataFrame> {
+        let mut long = melt_sensor_lists(&self.0, &["left", "right"])?;
+        let side = long
+            .column("column")?
+            .utf8()?
+            .into_iter()
+            .map(|x| match x {
+                Some("left") => "l",
+                _ => "r",
+            })
+            .collect::<Vec<&str>>();
+        long.replace("column", Series::new("side", side))?;
+        long.rename("column", "side")?;
+        long.rename("index", "sensor_index")?;
+        Ok(long)
+    }
+
     pub fn calc_posture_distribution(&self, n: usize) -> NDHistogram {
         let p = self.calc_angles();
         NDHistogram::new(
             vec![
                 p.par_iter()
-                    .map(|x| x.alpha.iter().take(9).sum())
+                    .map(|x| upper_bend(&x.alpha))
                     .collect::<Vec<f64>>(),
                 self.acc()
                     .into_iter()
@@ -125,7 +615,7 @@ impl RawDf {
     }
 
     pub fn with_coordinates(&self) -> PolarsResult<Self> {
-        let angles = timeit(|| self.calc_angles());
+        let angles = self.calc_angles();
         let mut df = (*self).clone();
         let mut df = df
             .replace_or_add(
@@ -161,9 +651,47 @@ impl RawDf {
         ))
     }
 
+    /// Same output as [`RawDf::with_coordinates`], but only `chunk_rows` rows are
+    /// calculated at a time to cap the memory of the intermediate angle vectors.
+    pub fn with_coordinates_chunked(&self, chunk_rows: usize) -> PolarsResult<Self> {
+        let mut df: Option<DataFrame> = None;
+        for offset in (0..self.0.height()).step_by(chunk_rows.max(1)) {
+            let chunk = RawDf(self.0.slice(offset as i64, chunk_rows.max(1))).with_coordinates()?;
+            match df.as_mut() {
+                Some(df) => {
+                    df.vstack_mut(&chunk.0)?;
+                }
+                None => df = Some(chunk.0),
+            }
+        }
+        match df {
+            Some(mut df) => {
+                df.align_chunks();
+                Ok(RawDf(df))
+            }
+            None => self.with_coordinates(),
+        }
+    }
+
+    /// movement over the last 15 samples, see [`RawDf::with_movement_score_with`]
     pub fn with_movement_score(&self) -> RawDf {
-        let mut v = vec![0.0; 15];
-        v.append(&mut self.calc_movement_score(15));
+        self.with_movement_score_with(15, WindowAlignment::Trailing)
+    }
+
+    /// The score of a window of `n` acc differences covers `n + 1` rows and is placed
+    /// at the row given by `alignment`. Rows without a full window are 0, frames shorter
+    /// than the window get an all zero movement column.
+    pub fn with_movement_score_with(&self, n: usize, alignment: WindowAlignment) -> RawDf {
+        let height = self.0.height();
+        let mut score = self.calc_movement_score(n);
+        let lead = match alignment {
+            WindowAlignment::Trailing => n,
+            WindowAlignment::Centered => n / 2,
+        }
+        .min(height);
+        let mut v = vec![0.0; lead];
+        v.append(&mut score);
+        v.resize(height, 0.0);
 
         RawDf(
             self.0
@@ -174,14 +702,13 @@ impl RawDf {
         )
     }
 
+    /// one value per window of `n` consecutive acc differences, in row order
     pub fn calc_movement_score(&self, n: usize) -> Vec<f64> {
         self.acc().to_vec_unchecked()[..]
-            .windows(2)
-            .par_bridge()
+            .par_windows(2)
             .map(|x| [x[1][0] - x[0][0], x[1][1] - x[0][1], x[1][2] - x[0][2]].map(|x| x.abs()))
             .collect::<Vec<[i32; 3]>>()[..]
-            .windows(n)
-            .par_bridge()
+            .par_windows(n)
             .map(|v| {
                 (v.into_iter().map(|v| v[0] + v[1] + v[2]).sum::<i32>() as f64 / n as f64) / 8.0
             })
@@ -196,7 +723,9 @@ impl RawDf {
             v[2].to_vec().into_iter().map(|x| x.unwrap()).collect(),
             v[3].to_vec().into_iter().map(|x| x.unwrap()).collect(),
             match v[4] {
+                AnyValue::Int16(v) => v,
                 AnyValue::Int32(v) => v as i16,
+                AnyValue::Int64(v) => v as i16,
                 _ => 0,
             },
             match v[5] {
@@ -213,7 +742,11 @@ impl TryFrom<DataFrame> for RawDf {
 
     fn try_from(value: DataFrame) -> Result<RawDf, Self::Error> {
         if let OutputType::raw = infer_df_type(&value) {
-            Ok(RawDf(transform_to_new_schema(&mut value.clone())?.clone()))
+            let mut value = with_i32_voltage(value)?;
+            if is_new_schema(&value) {
+                return Ok(RawDf(value));
+            }
+            Ok(RawDf(transform_to_new_schema(&mut value)?))
         } else {
             Err(PolarsError::SchemaMismatch(
                 format!("type infered to {:?}", infer_df_type(&value)).into(),
@@ -229,7 +762,7 @@ impl TryFrom<PathBuf> for RawDf {
         if value.is_dir() {
             create_user_df(&vec![value], OutputType::raw, None)
         } else {
-            read_input_file_into_df(value)
+            read_input_file_into_df(value).map_err(PolarsError::from)
         }?
         .try_into()
     }
@@ -242,3 +775,425 @@ impl TryFrom<Vec<PathBuf>> for RawDf {
         create_user_df_from_files(files, OutputType::raw, None)?.try_into()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use polars::prelude::*;
+
+    use super::{upper_bend, RawDf, WindowAlignment};
+    use crate::{
+        df::{StuckSensorDetection, SusLevel, ValidationConfig},
+        series::ToVec,
+    };
+
+    pub(crate) fn list_series(name: &str, rows: &[Vec<i32>]) -> Series {
+        Series::new(
+            name,
+            rows.iter()
+                .map(|x| Series::new("", x))
+                .collect::<Vec<Series>>(),
+        )
+    }
+
+    /// new schema raw df with `n` sensors per side, sampled every `step_ms`
+    pub(crate) fn fixture(acc: Vec<Vec<i32>>, n: usize, step_ms: i64) -> RawDf {
+        let rows = acc.len();
+        let sensors: Vec<Vec<i32>> = (0..rows).map(|i| vec![i as i32; n]).collect();
+        RawDf(
+            DataFrame::new(vec![
+                list_series("left", &sensors),
+                list_series("right", &sensors),
+                list_series("acc", &acc),
+                list_series("gyro", &vec![vec![0; 3]; rows]),
+                Series::new("v", vec![4000; rows]),
+                Series::new(
+                    "t",
+                    (0..rows as i64)
+                        .map(|i| 1_680_000_000_000 + i * step_ms)
+                        .collect::<Vec<i64>>(),
+                )
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                .unwrap(),
+            ])
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn split_on_voltage_step() {
+        let mut df = fixture(vec![vec![0, 0, 1000]; 6], 9, 20);
+        df.0.with_column(Series::new("v", [4000, 4010, 3995, 3500, 3510, 3490]))
+            .unwrap();
+
+        let parts = df.split_on_voltage_jump(100);
+
+        assert_eq!(
+            parts.iter().map(|x| x.height()).collect::<Vec<usize>>(),
+            vec![3, 3]
+        );
+        assert_eq!(df.split_on_voltage_jump(1000).len(), 1);
+    }
+
+    #[test]
+    fn head_tail_and_slice() {
+        let df = fixture((0..5).map(|i| vec![i, 0, 1000]).collect(), 9, 20);
+
+        let head = df.head(2);
+        assert_eq!(head.height(), 2);
+        assert_eq!(head.acc_magnitude().len(), 2);
+        assert_eq!(
+            df.tail(2).acc().get(0).unwrap().i32().unwrap().get(0),
+            Some(3)
+        );
+        assert_eq!(df.slice(1, 3).height(), 3);
+    }
+
+    #[test]
+    fn sensor_count_from_lists() {
+        assert_eq!(fixture(vec![vec![0, 0, 1000]; 3], 9, 20).sensor_count(), 9);
+        assert_eq!(
+            fixture(vec![vec![0, 0, 1000]; 3], 18, 20).sensor_count(),
+            18
+        );
+    }
+
+    #[test]
+    fn sensor_count_of_flat_frame_with_derived_columns() {
+        let mut df = df![
+            "l1" => [1000], "l2" => [1000], "r1" => [1000], "r2" => [1000], "v" => [4000],
+            "t" => [1_680_000_000_000i64]
+        ]
+        .unwrap();
+        for name in ["score", "voltage", "gyro_x", "gyro_y", "gyro_z"] {
+            df.with_column(Series::new(name, [0.0])).unwrap();
+        }
+
+        assert_eq!(RawDf(df).sensor_count(), 2);
+    }
+
+    #[test]
+    fn gyro_magnitude_per_row() {
+        let mut df = fixture(vec![vec![0, 0, 1000]; 3], 9, 20);
+        df.0.replace(
+            "gyro",
+            list_series("gyro", &[vec![3, 4, 0], vec![1, 2, 2], vec![1, 2]]),
+        )
+        .unwrap();
+
+        let df = df.with_gyro_magnitude();
+
+        assert_eq!(
+            df.0["gyro_mag"]
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<Option<f64>>>(),
+            vec![Some(5.0), Some(3.0), None]
+        );
+    }
+
+    #[test]
+    fn validate_in_memory() {
+        let mut df = fixture(vec![vec![0, 0, 1000]; 100], 9, 20);
+        let config = ValidationConfig {
+            stuck_sensors: StuckSensorDetection { min_rows: 100 },
+            ..Default::default()
+        };
+        let clean = df.validate(&config);
+        assert_eq!(clean.level, SusLevel::Ok);
+        assert!(clean.stuck_sensors.is_empty());
+
+        let left = (0..100)
+            .map(|i| match i {
+                10 | 20 | 30 => vec![900; 9],
+                i => vec![i; 9],
+            })
+            .collect::<Vec<Vec<i32>>>();
+        df.0.replace("left", list_series("left", &left)).unwrap();
+        df.0.replace("right", list_series("right", &vec![vec![7; 9]; 100]))
+            .unwrap();
+
+        let report = df.validate(&config);
+
+        assert!(matches!(report.level, SusLevel::TurboSus(_)));
+        assert_eq!(report.stuck_sensors.len(), 9);
+        assert_eq!(report.stuck_sensors[0].column, "r1");
+        assert_eq!(report.stuck_sensors[0].value, 7);
+    }
+
+    #[test]
+    fn downsample_every_nth_row() {
+        let df = fixture((0..100).map(|i| vec![i, 0, 1000]).collect(), 9, 20);
+
+        let preview = df.downsample_every_nth(10);
+
+        assert_eq!(preview.height(), 10);
+        assert_eq!(
+            preview.acc().get(1).unwrap().i32().unwrap().get(0),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn upper_bend_for_any_sensor_count() {
+        assert_eq!(upper_bend(&[1.0; 18]), 9.0);
+        assert_eq!(upper_bend(&[1.0; 12]), 6.0);
+        assert_eq!(upper_bend(&[]), 0.0);
+    }
+
+    #[test]
+    fn bend_column_per_row() {
+        let df = fixture(vec![vec![0, 0, 1000]; 5], 18, 20).with_bend();
+
+        assert_eq!(df.0["bend"].len(), df.height());
+        assert_eq!(df.0["bend"].dtype(), &DataType::Float64);
+    }
+
+    #[test]
+    fn concat_sorted_by_time() {
+        let early = fixture(vec![vec![0, 0, 1000]; 3], 9, 20);
+        let mut late = fixture(vec![vec![0, 0, 1000]; 2], 9, 20);
+        late.0
+            .with_column(
+                Series::new("t", [1_680_000_001_000i64, 1_680_000_001_020])
+                    .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let df = RawDf::concat(&[late, early.clone()]).unwrap();
+        let t = df.time().into_iter().collect::<Vec<Option<i64>>>();
+
+        assert_eq!(df.height(), 5);
+        assert!(t.windows(2).all(|x| x[0] <= x[1]));
+        assert!(RawDf::concat(&[early.clone(), early.with_bend()]).is_err());
+        assert!(RawDf::concat(&[]).is_err());
+    }
+
+    #[test]
+    fn voltage_of_other_integer_widths() {
+        let mut df = fixture(vec![vec![0, 0, 1000]; 3], 9, 20);
+        df.0.with_column(Series::new("v", [4000i64, 4010, 3990]))
+            .unwrap();
+
+        assert_eq!(df.voltage().get(1), Some(4010));
+        assert!(df.get_measurement_idx(0).is_some());
+
+        let converted = RawDf::try_from(df.0).unwrap();
+        assert_eq!(converted.0["v"].dtype(), &DataType::Int32);
+    }
+
+    #[test]
+    fn movement_spike_alignment() {
+        let mut acc = vec![vec![0, 0, 1000]; 60];
+        acc[30] = vec![800, 0, 1000];
+        let df = fixture(acc, 9, 20);
+        let moving_rows = |df: RawDf| {
+            df.0["movement"]
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .enumerate()
+                .filter(|x| x.1 > 0.0)
+                .map(|x| x.0)
+                .collect::<Vec<usize>>()
+        };
+
+        let trailing = moving_rows(df.with_movement_score_with(4, WindowAlignment::Trailing));
+        assert_eq!(trailing.first(), Some(&30));
+        assert_eq!(trailing.last(), Some(&34));
+
+        let centered = moving_rows(df.with_movement_score_with(4, WindowAlignment::Centered));
+        assert_eq!(centered, vec![28, 29, 30, 31, 32]);
+
+        assert_eq!(df.with_movement_score().0["movement"].len(), 60);
+    }
+
+    #[test]
+    fn nearest_row_within_distance() {
+        let df = fixture(vec![vec![0, 0, 1000]; 5], 9, 1000);
+        let t0 = NaiveDateTime::from_timestamp_millis(1_680_000_000_000).unwrap();
+        let at = |ms: i64| t0 + Duration::milliseconds(ms);
+        let max = Duration::seconds(1);
+
+        assert_eq!(df.nearest_row(at(2300), max), Some(2));
+        assert_eq!(df.nearest_row(at(2700), max), Some(3));
+        assert_eq!(df.nearest_row(at(10_000), max), None);
+        assert!(df.measurement_at(t0, max).is_some());
+    }
+
+    #[test]
+    fn posture_timeseries_pairs_rows() {
+        let mut df = fixture(vec![vec![0, 0, 1000]; 4], 18, 20);
+        let t0 = 1_680_000_000_000i64;
+        df.0.with_column(
+            Series::new("t", [Some(t0), None, Some(t0 + 40), Some(t0 + 60)])
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                .unwrap(),
+        )
+        .unwrap();
+
+        let (t, bend) = df.posture_timeseries();
+
+        assert_eq!(t.len(), bend.len());
+        assert_eq!(t.len(), 3);
+    }
+
+    #[test]
+    fn chunked_coordinates_match() {
+        let df = fixture(vec![vec![0, 0, 1000]; 50], 18, 20);
+
+        let whole = df.with_coordinates().unwrap();
+        let chunked = df.with_coordinates_chunked(7).unwrap();
+
+        assert!(chunked.0.frame_equal(&whole.0));
+    }
+
+    #[test]
+    fn measurements_round_trip() {
+        let df = fixture(vec![vec![1, 2, 3], vec![4, 5, 6]], 9, 20);
+        let measurements = (0..df.height())
+            .map(|i| df.get_measurement_idx(i).unwrap())
+            .collect();
+
+        let rebuilt = RawDf::from_measurements(measurements).unwrap();
+        assert!(rebuilt.0.frame_equal(&df.0));
+
+        let again = (0..rebuilt.height())
+            .map(|i| rebuilt.get_measurement_idx(i).unwrap())
+            .collect();
+        assert!(RawDf::from_measurements(again)
+            .unwrap()
+            .0
+            .frame_equal(&df.0));
+    }
+
+    #[test]
+    fn estimate_steps_on_sinusoid() {
+        // 10 s at 50 Hz with two steps per second
+        let acc = (0..500)
+            .map(|i| {
+                let t = i as f64 * 0.02;
+                vec![
+                    0,
+                    0,
+                    (1000.0 + 300.0 * (2.0 * std::f64::consts::PI * 2.0 * t).sin()) as i32,
+                ]
+            })
+            .collect();
+
+        let df = fixture(acc, 18, 20);
+        let windows = df.step_windows(5000);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(
+            windows.iter().map(|x| x.data).collect::<Vec<usize>>(),
+            vec![10, 10]
+        );
+
+        let days = df.estimate_steps(5000);
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].time, windows[0].time.date());
+        assert_eq!(days[0].data, 20);
+    }
+
+    #[test]
+    fn integrate_constant_gyro() {
+        let mut df = fixture(vec![vec![0, 0, 0]; 11], 18, 100);
+        df.0.replace_or_add("gyro", list_series("gyro", &vec![vec![10, 0, -5]; 11]))
+            .unwrap();
+
+        let angles = df.integrate_gyro();
+
+        assert_eq!(angles.len(), 11);
+        assert!((angles.last().unwrap()[0] - 10.0).abs() < 1e-9);
+        assert!((angles.last().unwrap()[2] + 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_format_row_count() {
+        let long = fixture(vec![vec![0, 0, 1000]; 5], 9, 20).to_long().unwrap();
+
+        assert_eq!(long.height(), 5 * 9 * 2);
+        assert_eq!(
+            long.get_column_names(),
+            vec!["t", "side", "sensor_index", "value"]
+        );
+    }
+
+    #[test]
+    fn movement_score_of_short_frame() {
+        let df = fixture(vec![vec![0, 0, 1000]; 3], 18, 20).with_movement_score();
+        let movement: Vec<f64> = df["movement"].to_vec_unchecked();
+
+        assert_eq!(movement, vec![0.0; 3]);
+    }
+
+    #[test]
+    fn resample_irregular_frame() {
+        let mut df = fixture(vec![vec![0, 0, 0], vec![150, 0, 0], vec![400, 0, 0]], 18, 1);
+        let t0 = 1_680_000_000_000i64;
+        df.0.replace_or_add(
+            "t",
+            Series::new("t", vec![t0, t0 + 150, t0 + 400])
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                .unwrap(),
+        )
+        .unwrap();
+
+        let resampled = df.resample_regular(100, 1000).unwrap();
+        let t: Vec<i64> = resampled["t"].to_vec_unchecked();
+
+        assert_eq!(t, (0..5).map(|i| t0 + i * 100).collect::<Vec<i64>>());
+        assert_eq!(
+            resampled
+                .acc()
+                .to_vec_unchecked()
+                .into_iter()
+                .map(|x| x[0])
+                .collect::<Vec<i32>>(),
+            vec![0, 100, 200, 300, 400]
+        );
+    }
+
+    #[test]
+    fn resample_carries_extra_columns() {
+        let mut df = fixture(
+            vec![vec![0, 0, 0], vec![100, 0, 0], vec![200, 0, 0]],
+            18,
+            200,
+        );
+        df.0.replace_or_add(
+            "gyro",
+            list_series("gyro", &[vec![0, 0, 0], vec![20, 0, 0], vec![40, 0, 0]]),
+        )
+        .unwrap();
+        df.0.with_column(Series::new("label", ["a", "b", "c"]))
+            .unwrap();
+
+        let resampled = df.resample_regular(100, 1000).unwrap();
+
+        assert_eq!(
+            resampled.get_column_names(),
+            vec!["left", "right", "acc", "gyro", "v", "t", "label"]
+        );
+        assert_eq!(
+            resampled
+                .gyro()
+                .to_vec_unchecked()
+                .into_iter()
+                .map(|x| x[0])
+                .collect::<Vec<i32>>(),
+            vec![0, 10, 20, 30, 40]
+        );
+        assert_eq!(
+            resampled["label"]
+                .utf8()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<Option<&str>>>(),
+            vec![Some("a"), Some("a"), Some("b"), Some("b"), Some("c")]
+        );
+    }
+}
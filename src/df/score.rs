@@ -1,20 +1,26 @@
-use std::path::PathBuf;
+use std::{io::Write, path::PathBuf};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
 use polars::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    clustered_data::NDHistogram,
     misc::infer_df_type,
     schema::{OutputType, ScoreDfJS},
     series::ToVec,
+    utils::stats_utils::Diff,
 };
 
 use derive_more::Deref;
 
-use super::{convert_i64_to_time, create_user_df, read_csv_file};
+use super::{
+    concat_sorted, convert_i64_to_time_tz, create_user_df, ndjson_string, read_csv_file,
+    write_ndjson,
+};
 
-#[derive(Debug, Deref)]
+#[derive(Debug, Clone, Deref)]
 pub struct ScoreDf(pub DataFrame);
 
 impl ScoreDf {
@@ -22,6 +28,11 @@ impl ScoreDf {
         ScoreDf(df)
     }
 
+    /// stacks frames with the same schema, sorted by time
+    pub fn concat(frames: &[ScoreDf]) -> PolarsResult<ScoreDf> {
+        Ok(ScoreDf(concat_sorted(frames.iter().map(|x| &x.0))?))
+    }
+
     pub fn dummy() -> ScoreDf {
         //let schema = OutputType::points.schema(None);
         let t: Vec<NaiveDateTime> = vec![];
@@ -40,8 +51,10 @@ impl ScoreDf {
         )
     }
 
-    fn convert_t_to_time(&mut self) {
-        if let Ok(df) = convert_i64_to_time(&mut self.0, None) {
+    /// casts an `i64` time column to a datetime in `tz`, `None` falls back to
+    /// `DEFAULT_TIMEZONE`
+    pub fn convert_t_to_time(&mut self, tz: Option<&str>) {
+        if let Ok(df) = convert_i64_to_time_tz(&mut self.0, None, tz) {
             self.0 = df.to_owned();
         }
     }
@@ -50,17 +63,162 @@ impl ScoreDf {
         ScoreDfJS::from(self.0)
     }
 
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&ScoreDfJS::from(self.0.clone()))
+    }
+
+    pub fn to_ndjson(&self) -> PolarsResult<String> {
+        ndjson_string(&self.0)
+    }
+
+    /// streams the rows as ndjson, see [`write_ndjson`]
+    pub fn write_ndjson<W: Write>(&self, writer: W) -> PolarsResult<()> {
+        write_ndjson(&self.0, writer)
+    }
+
     pub fn summary(&self) -> ScoreDfSummary {
         let col = self.0.column("score").unwrap();
 
         ScoreDfSummary {
-            average_score: col.mean().unwrap_or(50.0),
+            average_score: col.mean(),
+            duration: col.len() as u32,
+            max: col.max(),
+            min: col.min(),
+        }
+    }
+
+    /// One row per day with data, columns `date`, `avg_score`, `duration_s`, `min` and
+    /// `max` from the [`ScoreDf::summary`] of the day. The days follow the timezone of
+    /// the `t` column, frames without one are split at midnight UTC.
+    pub fn daily_summary_frame(&self) -> PolarsResult<DataFrame> {
+        let tz = match self.0["t"].dtype() {
+            DataType::Datetime(_, Some(tz)) => tz.parse::<Tz>().ok(),
+            _ => None,
+        };
+        let dates = self
+            .time()
+            .into_iter()
+            .map(|t| {
+                let t = NaiveDateTime::from_timestamp_millis(t?)?;
+                Some(match tz {
+                    Some(tz) => tz.from_utc_datetime(&t).date_naive(),
+                    None => t.date(),
+                })
+            })
+            .collect::<Vec<Option<NaiveDate>>>();
+        let mut days = dates.iter().flatten().cloned().collect::<Vec<NaiveDate>>();
+        days.sort();
+        days.dedup();
+
+        let summaries = days
+            .iter()
+            .map(|day| {
+                let mask: BooleanChunked = dates.iter().map(|x| *x == Some(*day)).collect();
+                Ok(ScoreDf(self.0.filter(&mask)?).summary())
+            })
+            .collect::<PolarsResult<Vec<ScoreDfSummary>>>()?;
+
+        DataFrame::new(vec![
+            DateChunked::from_naive_date("date", days.iter().cloned()).into_series(),
+            Series::new(
+                "avg_score",
+                summaries
+                    .iter()
+                    .map(|x| x.average_score)
+                    .collect::<Vec<Option<f64>>>(),
+            ),
+            Series::new(
+                "duration_s",
+                summaries.iter().map(|x| x.duration).collect::<Vec<u32>>(),
+            ),
+            Series::new(
+                "min",
+                summaries
+                    .iter()
+                    .map(|x| x.min)
+                    .collect::<Vec<Option<f64>>>(),
+            ),
+            Series::new(
+                "max",
+                summaries
+                    .iter()
+                    .map(|x| x.max)
+                    .collect::<Vec<Option<f64>>>(),
+            ),
+        ])
+    }
+
+    /// Inserts rows with null values every `interval_ms` into gaps of at most `max_gap_ms`,
+    /// so charts don't connect across them. Longer gaps are left as breaks.
+    pub fn fill_time_gaps(&self, interval_ms: i64, max_gap_ms: i64) -> PolarsResult<ScoreDf> {
+        if interval_ms <= 0 {
+            return Err(PolarsError::ComputeError(
+                "fill interval has to be positive".into(),
+            ));
+        }
+        let df = self.0.sort(["t"], false)?;
+        let t = df["t"]
+            .datetime()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<i64>>();
+
+        let gaps: Vec<i64> = t.iter().copied().diff();
+        let mut missing: Vec<i64> = vec![];
+        for (i, gap) in gaps.into_iter().enumerate() {
+            if gap > interval_ms && gap <= max_gap_ms {
+                missing.extend(
+                    (1..)
+                        .map(|k| t[i] + k * interval_ms)
+                        .take_while(|x| *x < t[i + 1]),
+                );
+            }
+        }
+        if missing.is_empty() {
+            return Ok(ScoreDf(df));
+        }
+
+        let n = missing.len();
+        let filler = DataFrame::new(
+            df.get_columns()
+                .iter()
+                .map(|x| match x.name() {
+                    "t" => Series::new("t", missing.clone()).cast(x.dtype()),
+                    name => Ok(Series::full_null(name, n, x.dtype())),
+                })
+                .collect::<PolarsResult<Vec<Series>>>()?,
+        )?;
+        Ok(ScoreDf(df.vstack(&filler)?.sort(["t"], false)?))
+    }
+
+    /// Like [`ScoreDf::summary`] but scores outside of `valid` (inclusive) are ignored.
+    /// Valid scores are in `0..=100`, anything else is a sentinel or garbage value.
+    pub fn summary_clamped(&self, valid: (f64, f64)) -> ScoreDfSummary {
+        let col = Series::new(
+            "score",
+            self.score()
+                .into_iter()
+                .map(|x| x.filter(|x| *x >= valid.0 && *x <= valid.1))
+                .collect::<Vec<Option<f64>>>(),
+        );
+
+        ScoreDfSummary {
+            average_score: col.mean(),
             duration: col.len() as u32,
-            max: col.max().unwrap_or(0.0),
-            min: col.min().unwrap_or(0.0),
+            max: col.max(),
+            min: col.min(),
         }
     }
 
+    /// histogram of the measured scores, rows without a score are skipped
+    pub fn score_histogram(&self, n: usize) -> PolarsResult<NDHistogram> {
+        NDHistogram::from_series(&[self.0.column("score")?.clone()], n)
+    }
+
+    pub fn has_activity(&self) -> bool {
+        self.0.column("activity").is_ok()
+    }
+
     fn score(&self) -> Vec<Option<f64>> {
         self.0.column("score").to_vec()
     }
@@ -74,11 +232,13 @@ impl ScoreDf {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScoreDfSummary {
-    pub average_score: f64,
+    /// `None` if there is no valid score
+    pub average_score: Option<f64>,
     // in seconds
     pub duration: u32,
-    pub min: f64,
-    pub max: f64,
+    /// `None` if there is no valid score, like `average_score`
+    pub min: Option<f64>,
+    pub max: Option<f64>,
 }
 
 impl Into<ScoreDfSummary> for ScoreDf {
@@ -117,3 +277,246 @@ impl TryFrom<PathBuf> for ScoreDf {
         .try_into()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::NaiveDate;
+    use polars::prelude::*;
+
+    use super::{ScoreDf, ScoreDfSummary};
+
+    pub(crate) fn fixture(t: &[i64], score: Vec<Option<f64>>) -> ScoreDf {
+        ScoreDf(
+            DataFrame::new(vec![
+                Series::new("t", t)
+                    .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                    .unwrap(),
+                Series::new("score", score.clone()),
+                Series::new("posture", score),
+                Series::new("movement", vec![0.0; t.len()]),
+                Series::new("activity", vec!["office"; t.len()]),
+            ])
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn score_histogram_skips_missing_scores() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(
+            &[t0, t0 + 1000, t0 + 2000, t0 + 3000],
+            vec![Some(10.0), None, None, Some(90.0)],
+        );
+
+        // a default of 50 for the missing scores would end up in the upper basket
+        let h = df.score_histogram(2).unwrap();
+
+        assert_eq!(h.normalized(), vec![0.5, 0.5]);
+        assert_eq!(h.to_string(), "1,1");
+    }
+
+    #[test]
+    fn score_histogram_of_empty_and_null_frames() {
+        let t0 = 1_681_294_830_000;
+        let empty = fixture(&[], vec![]).score_histogram(2).unwrap();
+        let nulls = fixture(&[t0, t0 + 1000], vec![None, None])
+            .score_histogram(2)
+            .unwrap();
+
+        for h in [empty, nulls] {
+            assert_eq!(h.n(), 0);
+            assert_eq!(h.dim(), 0);
+            assert!(h.normalized().is_empty());
+        }
+    }
+
+    #[test]
+    fn convert_time_in_timezone() {
+        let df = || ScoreDf(df!["t" => [1_681_294_830_000i64], "score" => [80.0]].unwrap());
+        let (mut berlin, mut new_york) = (df(), df());
+
+        berlin.convert_t_to_time(None);
+        new_york.convert_t_to_time(Some("America/New_York"));
+
+        // 10:20 UTC
+        assert_eq!(berlin.time().hour().get(0), Some(12));
+        assert_eq!(new_york.time().hour().get(0), Some(6));
+    }
+
+    #[test]
+    fn activity_column_presence() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(&[t0, t0 + 1000], vec![Some(80.0), Some(60.0)]);
+        assert!(df.has_activity());
+
+        let without = ScoreDf(df.0.drop("activity").unwrap());
+        assert!(!without.has_activity());
+    }
+
+    #[test]
+    fn daily_summary_rows() {
+        let t0 = 1_681_294_830_000;
+        let day = 86_400_000;
+        let df = fixture(
+            &[t0, t0 + 1000, t0 + day],
+            vec![Some(80.0), Some(60.0), Some(90.0)],
+        );
+
+        let daily = df.daily_summary_frame().unwrap();
+
+        assert_eq!(daily.height(), 2);
+        assert_eq!(
+            daily.get_column_names(),
+            vec!["date", "avg_score", "duration_s", "min", "max"]
+        );
+        assert_eq!(daily["date"].dtype(), &DataType::Date);
+        assert_eq!(
+            daily["avg_score"]
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<Option<f64>>>(),
+            vec![Some(70.0), Some(90.0)]
+        );
+        assert_eq!(
+            daily["duration_s"]
+                .u32()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<u32>>(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn daily_summary_in_frame_timezone() {
+        // 10:00 and 22:30 UTC, the latter is already the next day in Berlin
+        let t0 = 1_681_293_600_000;
+        let mut df = fixture(&[t0, t0 + 45_000_000], vec![Some(80.0), Some(60.0)]);
+        df.0.with_column(
+            df.0["t"]
+                .cast(&DataType::Datetime(
+                    TimeUnit::Milliseconds,
+                    Some("Europe/Berlin".into()),
+                ))
+                .unwrap(),
+        )
+        .unwrap();
+
+        let daily = df.daily_summary_frame().unwrap();
+
+        assert_eq!(
+            daily["date"]
+                .date()
+                .unwrap()
+                .as_date_iter()
+                .collect::<Vec<Option<NaiveDate>>>(),
+            vec![
+                NaiveDate::from_ymd_opt(2023, 4, 12),
+                NaiveDate::from_ymd_opt(2023, 4, 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn all_null_scores_have_no_average() {
+        let t0 = 1_681_294_830_000;
+        let empty = fixture(&[t0, t0 + 1000], vec![None, None]).summary();
+        let valid = fixture(&[t0, t0 + 1000], vec![Some(80.0), Some(60.0)]).summary();
+
+        assert_eq!(empty.average_score, None);
+        assert_eq!(valid.average_score, Some(70.0));
+
+        let overall: ScoreDfSummary = vec![empty, valid].into();
+        assert_eq!(overall.average_score, Some(70.0));
+    }
+
+    #[test]
+    fn fill_short_gaps_with_nulls() {
+        let t0 = 1_681_294_830_000;
+        let minute = 60_000;
+        let df = fixture(
+            &[
+                t0,
+                t0 + 10 * minute,
+                t0 + 10 * minute + 1000,
+                t0 + 70 * minute,
+            ],
+            vec![Some(80.0); 4],
+        );
+
+        let filled = df.fill_time_gaps(1000, 15 * minute).unwrap();
+
+        // 599 rows inside the 10 minute gap, the hour long gap stays a break
+        assert_eq!(filled.height(), 4 + 599);
+        assert_eq!(filled["score"].null_count(), 599);
+        assert_eq!(filled.time().get(1), Some(t0 + 1000));
+        assert_eq!(filled.time().get(599), Some(t0 + 599_000));
+    }
+
+    #[test]
+    fn fill_rejects_non_positive_interval() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(&[t0, t0 + 10_000], vec![Some(80.0); 2]);
+
+        assert!(df.fill_time_gaps(0, 60_000).is_err());
+        assert!(df.fill_time_gaps(-1000, 60_000).is_err());
+    }
+
+    #[test]
+    fn concat_sorted_by_time() {
+        let t0 = 1_681_294_830_000;
+        let a = fixture(&[t0 + 2000, t0 + 3000], vec![Some(80.0); 2]);
+        let b = fixture(&[t0, t0 + 1000], vec![Some(60.0); 2]);
+
+        let df = ScoreDf::concat(&[a, b]).unwrap();
+
+        assert_eq!(df.height(), 4);
+        assert_eq!(df.time().get(0), Some(t0));
+        assert_eq!(df.time().get(3), Some(t0 + 3000));
+    }
+
+    #[test]
+    fn clamped_summary_ignores_sentinels() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(
+            &[t0, t0 + 1000, t0 + 2000],
+            vec![Some(80.0), Some(-999.0), Some(60.0)],
+        );
+
+        assert_eq!(df.summary().min, Some(-999.0));
+
+        let summary = df.summary_clamped((0.0, 100.0));
+        assert_eq!(summary.min, Some(60.0));
+        assert_eq!(summary.max, Some(80.0));
+        assert_eq!(summary.average_score, Some(70.0));
+
+        let empty = df.summary_clamped((200.0, 300.0));
+        assert_eq!(empty.min, None);
+        assert_eq!(empty.max, None);
+    }
+
+    #[test]
+    fn clamped_summary_of_empty_and_null_frames() {
+        let t0 = 1_681_294_830_000;
+        let empty = fixture(&[], vec![]).summary_clamped((0.0, 100.0));
+        let nulls = fixture(&[t0, t0 + 1000], vec![None, None]).summary_clamped((0.0, 100.0));
+
+        for (summary, duration) in [(empty, 0), (nulls, 2)] {
+            assert_eq!(summary.average_score, None);
+            assert_eq!(summary.duration, duration);
+            assert_eq!(summary.min, None);
+            assert_eq!(summary.max, None);
+        }
+    }
+
+    #[test]
+    fn score_df_json_keys() {
+        let json: serde_json::Value =
+            serde_json::from_str(&ScoreDf::dummy().to_json_string().unwrap()).unwrap();
+
+        for key in ["t", "score", "posture", "movement", "activity"] {
+            assert!(json.get(key).is_some(), "missing key {}", key);
+        }
+    }
+}
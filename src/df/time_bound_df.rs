@@ -1,11 +1,11 @@
 use std::{fmt::Debug, ops::Deref};
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use polars::prelude::*;
 
 use timespan::{DatedData, Timespan};
 
-use crate::schema::OutputType;
+use crate::{schema::OutputType, utils::stats_utils::Diff};
 
 use super::{generic::GenericTimeBoundDf, logs::LogsDf, raw::RawDf, score::ScoreDf};
 
@@ -15,10 +15,40 @@ pub trait TimeBoundDf {
     fn get_activity_timespans(&self, threshold: i64) -> Vec<Timespan>;
     fn split_into_time_chunks(&self, duration: i64) -> Vec<Box<Self>>;
     fn get_days(&self, min_length: Option<usize>) -> Vec<DatedData<Box<Self>>>;
+    fn get_weeks(&self, min_length: Option<usize>) -> Vec<DatedData<Box<Self>>>;
 }
 
 pub trait TimeColumn {
     fn time(&self) -> &Logical<DatetimeType, Int64Type>;
+
+    /// first sample of the (sorted) time column, `None` for empty frames
+    fn first_time(&self) -> Option<NaiveDateTime> {
+        match self.time().len() {
+            0 => None,
+            _ => self
+                .time()
+                .get(0)
+                .and_then(NaiveDateTime::from_timestamp_millis),
+        }
+    }
+
+    /// last sample of the (sorted) time column, `None` for empty frames
+    fn last_time(&self) -> Option<NaiveDateTime> {
+        match self.time().len() {
+            0 => None,
+            n => self
+                .time()
+                .get(n - 1)
+                .and_then(NaiveDateTime::from_timestamp_millis),
+        }
+    }
+
+    /// `"{min}_{max}"` of the time column formatted with `fmt`, `None` for empty frames
+    fn time_range_string(&self, fmt: &str) -> Option<String> {
+        let begin = NaiveDateTime::from_timestamp_millis(self.time().min()?)?;
+        let end = NaiveDateTime::from_timestamp_millis(self.time().max()?)?;
+        Some(format!("{}_{}", begin.format(fmt), end.format(fmt)))
+    }
 }
 
 impl TimeColumn for RawDf {
@@ -45,24 +75,57 @@ impl TimeColumn for GenericTimeBoundDf {
     }
 }
 
-pub trait Between {
-    fn between(&self, ts: Timespan) -> Self;
+/// Rebuilds the wrapper from a frame derived from `self`, e.g. after filtering rows.
+pub trait WithFrame: Sized {
+    fn with_frame(&self, df: DataFrame) -> PolarsResult<Self>;
+}
+
+impl WithFrame for RawDf {
+    fn with_frame(&self, df: DataFrame) -> PolarsResult<Self> {
+        df.try_into()
+    }
+}
+
+impl WithFrame for ScoreDf {
+    fn with_frame(&self, df: DataFrame) -> PolarsResult<Self> {
+        df.try_into()
+    }
+}
+
+impl WithFrame for GenericTimeBoundDf {
+    fn with_frame(&self, df: DataFrame) -> PolarsResult<Self> {
+        GenericTimeBoundDf::with_time_column(df, self.time_column())
+    }
+}
+
+pub trait Between: Sized {
+    /// panics if the filtered frame can't be converted, see [`Between::try_between`]
+    fn between(&self, ts: Timespan) -> Self {
+        self.try_between(ts)
+            .expect("could not convert df after between")
+    }
+    fn try_between(&self, ts: Timespan) -> PolarsResult<Self>;
 }
 
 impl<F> Between for F
 where
-    F: TimeColumn + TryFrom<DataFrame> + Deref<Target = DataFrame>,
+    F: TimeColumn + WithFrame + Deref<Target = DataFrame>,
 {
-    fn between(&self, ts: Timespan) -> Self {
+    fn try_between(&self, ts: Timespan) -> PolarsResult<Self> {
+        // rows without a time are never inside of the timespan
         let mask = self
             .time()
             .into_iter()
-            .map(|x| ts.is_inside(NaiveDateTime::from_timestamp_millis(x.unwrap()).unwrap()))
-            .collect();
-        match self.filter(&mask).unwrap().try_into() {
-            Ok(df) => df,
-            _ => panic!("could not convert df after between"),
-        }
+            .map(|x| match x {
+                Some(ms) => NaiveDateTime::from_timestamp_millis(ms)
+                    .map(|t| ts.is_inside(t))
+                    .ok_or_else(|| {
+                        PolarsError::ComputeError(format!("timestamp {} out of range", ms).into())
+                    }),
+                None => Ok(false),
+            })
+            .collect::<PolarsResult<BooleanChunked>>()?;
+        self.with_frame(self.filter(&mask)?)
     }
 }
 
@@ -116,7 +179,7 @@ where
             .map(|x| x.unwrap())
             .collect::<Vec<i64>>();
         v.sort();
-        let diff = v.windows(2).map(|x| x[1] - x[0]).collect::<Vec<i64>>();
+        let diff: Vec<i64> = v.iter().copied().diff();
 
         let mut last_index: usize = 0;
         let mut activity_blocks = vec![];
@@ -158,6 +221,41 @@ where
             None => vec![],
         }
     }
+
+    /// same as `get_days` but grouped into weeks, `time` is the monday of the week
+    fn get_weeks(&self, min_length: Option<usize>) -> Vec<DatedData<Box<Self>>> {
+        match self.timespan() {
+            Some(spans) => {
+                let mut weeks = spans
+                    .days()
+                    .into_iter()
+                    .map(|x| x - Duration::days(x.weekday().num_days_from_monday() as i64))
+                    .collect::<Vec<NaiveDate>>();
+                weeks.dedup();
+                weeks
+                    .into_iter()
+                    .filter_map(|x| {
+                        let r = DatedData {
+                            time: x,
+                            data: Box::new(
+                                self.between(Timespan {
+                                    begin: x.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
+                                    end: (x + Duration::days(6))
+                                        .and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()),
+                                }),
+                            ),
+                        };
+                        if r.data.height() > min_length.unwrap_or(0) {
+                            Some(r)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
 }
 
 trait DataFrameType {
@@ -176,3 +274,123 @@ impl DataFrameType for ScoreDf {
 }
 
 pub struct TimeBoundDfEmpty;
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use polars::prelude::*;
+    use timespan::Timespan;
+
+    use super::{Between, TimeBoundDf, TimeColumn};
+    use crate::df::{raw::tests::fixture as raw_fixture, score::tests::fixture};
+
+    #[test]
+    fn first_and_last_time() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(&[t0, t0 + 1000, t0 + 2000], vec![Some(80.0); 3]);
+
+        assert_eq!(df.first_time(), NaiveDateTime::from_timestamp_millis(t0));
+        assert_eq!(
+            df.last_time(),
+            NaiveDateTime::from_timestamp_millis(t0 + 2000)
+        );
+
+        let empty = fixture(&[], vec![]);
+        assert_eq!(empty.first_time(), None);
+        assert_eq!(empty.last_time(), None);
+    }
+
+    #[test]
+    fn time_range_string() {
+        // 2023-04-12 10:20:30 and 10:21:30 UTC, unsorted
+        let t0 = 1_681_294_830_000;
+        let df = fixture(&[t0 + 60_000, t0], vec![Some(80.0); 2]);
+
+        assert_eq!(
+            df.time_range_string("%Y%m%dT%H%M%S"),
+            Some("20230412T102030_20230412T102130".to_string())
+        );
+        assert_eq!(fixture(&[], vec![]).time_range_string("%Y"), None);
+    }
+
+    #[test]
+    fn between_keeps_derived_raw_columns() {
+        // 8 columns, converting this again like an old schema frame fails
+        let df = raw_fixture(vec![vec![0, 0, 1000]; 10], 9, 1000)
+            .with_bend()
+            .with_acc_magnitude();
+        let t0 = df.first_time().unwrap();
+
+        let slice = df
+            .try_between(Timespan {
+                begin: t0,
+                end: t0 + chrono::Duration::milliseconds(4500),
+            })
+            .unwrap();
+
+        assert_eq!(slice.height(), 5);
+        assert_eq!(slice.get_column_names(), df.get_column_names());
+    }
+
+    #[test]
+    fn try_between_skips_null_and_rejects_corrupt_times() {
+        let t0 = 1_681_294_830_000;
+        let ts = || Timespan {
+            begin: NaiveDateTime::from_timestamp_millis(t0).unwrap(),
+            end: NaiveDateTime::from_timestamp_millis(t0 + 5000).unwrap(),
+        };
+        let mut df = fixture(&[t0, t0 + 1000, t0 + 2000], vec![Some(80.0); 3]);
+        df.0.replace(
+            "t",
+            Series::new("t", [Some(t0), None, Some(t0 + 2000)])
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(df.try_between(ts()).unwrap().height(), 2);
+
+        let corrupt = fixture(&[t0, i64::MAX], vec![Some(80.0); 2]);
+        assert!(matches!(
+            corrupt.try_between(ts()),
+            Err(PolarsError::ComputeError(_))
+        ));
+    }
+
+    #[test]
+    fn activity_timespans_split_by_threshold() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(
+            &[t0, t0 + 60_000, t0 + 120_000, t0 + 600_000],
+            vec![Some(80.0); 4],
+        );
+
+        assert_eq!(df.get_activity_timespans(300_000).len(), 2);
+        assert_eq!(df.get_activity_timespans(30_000).len(), 4);
+    }
+
+    #[test]
+    fn weeks_aggregate_days() {
+        // wednesday 2023-04-12, the following thursday and monday
+        let t0 = 1_681_294_830_000;
+        let day = 86_400_000;
+        let df = fixture(&[t0, t0 + day, t0 + 5 * day], vec![Some(80.0); 3]);
+
+        let weeks = df.get_weeks(None);
+
+        assert_eq!(
+            weeks
+                .iter()
+                .map(|x| x.time.to_string())
+                .collect::<Vec<String>>(),
+            vec!["2023-04-10", "2023-04-17"]
+        );
+        assert_eq!(
+            weeks
+                .iter()
+                .map(|x| x.data.height())
+                .collect::<Vec<usize>>(),
+            vec![2, 1]
+        );
+    }
+}
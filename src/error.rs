@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use polars::prelude::PolarsError;
+use thiserror::Error;
+
+/// Error of the public read and write functions.
+#[derive(Debug, Error)]
+pub enum DataError {
+    #[error("polars: {0}")]
+    Polars(#[from] PolarsError),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not detect the table format of {0:?}")]
+    UnknownFormat(PathBuf),
+    /// the file could be read but its header or timestamps are invalid
+    #[error("parse: {0}")]
+    Parse(String),
+}
+
+pub type DataResult<T> = Result<T, DataError>;
+
+/// lets `?` be used on a [`DataResult`] inside functions returning a `PolarsResult`
+impl From<DataError> for PolarsError {
+    fn from(value: DataError) -> Self {
+        match value {
+            DataError::Polars(e) => e,
+            DataError::Io(e) => PolarsError::Io(e),
+            e => PolarsError::ComputeError(e.to_string().into()),
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use std::{
-    collections::HashMap,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    io::Read,
     str::FromStr,
 };
 
 use enum_iterator::{all, Sequence};
 use serde::{Deserialize, Serialize};
-use serde_json::Number;
+use serde_json::{Number, Value};
+use timespan::TimedData;
 
 #[allow(
     dead_code,
@@ -63,6 +66,13 @@ impl FromStr for RectifyFeedback {
     }
 }
 
+impl RectifyFeedback {
+    /// parses the json directly from a file or stream
+    pub fn from_reader(reader: impl Read) -> serde_json::Result<Self> {
+        serde_json::from_reader::<_, RectifyFeedback>(reader)
+    }
+}
+
 impl Display for RectifyFeedback {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -298,6 +308,172 @@ pub struct BackpainFeedback {
 #[derive(Debug, PartialEq, Eq)]
 pub struct FeedbackParseError;
 
+/// json value with sorted keys and all numbers as f64, so `1` and `1.0` are the same answer
+fn normalized_value(value: Value) -> Value {
+    match value {
+        Value::Number(n) => n.as_f64().map(Value::from).unwrap_or(Value::Null),
+        Value::Array(v) => Value::Array(v.into_iter().map(normalized_value).collect()),
+        Value::Object(m) => {
+            let mut entries = m.into_iter().collect::<Vec<(String, Value)>>();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, normalized_value(v)))
+                    .collect(),
+            )
+        }
+        v => v,
+    }
+}
+
+/// Compares feedback by the given answers, independent of how numbers were encoded.
+pub trait FeedbackContent: Serialize {
+    fn content(&self) -> Value {
+        normalized_value(serde_json::to_value(self).unwrap_or(Value::Null))
+    }
+
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.content().to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl FeedbackContent for RectifyFeedback {}
+impl FeedbackContent for BackpainFeedback {}
+
+impl PartialEq for RectifyFeedback {
+    fn eq(&self, other: &Self) -> bool {
+        self.content() == other.content()
+    }
+}
+
+impl PartialEq for BackpainFeedback {
+    fn eq(&self, other: &Self) -> bool {
+        self.content() == other.content()
+    }
+}
+
+/// Numeric encoding of the answers for statistics.
+pub trait NumericFeedback {
+    /// names of the values returned by `to_numeric_vec`
+    fn numeric_columns() -> Vec<&'static str>;
+    /// choices as their position (`None` for `na`), numbers as f64
+    fn to_numeric_vec(&self) -> Vec<Option<f64>>;
+}
+
+fn choice<T: Numbering>(x: &T) -> Option<f64> {
+    match x.number() {
+        -1 => None,
+        i => Some(i as f64),
+    }
+}
+
+impl NumericFeedback for RectifyFeedback {
+    fn numeric_columns() -> Vec<&'static str> {
+        vec![
+            "shirtComfort",
+            "sensorIsMoving",
+            "shirtWearDuration",
+            "shirtWearWeekly",
+            "rectifyDuration",
+            "appUsability",
+            "rectifyBenefit",
+            "vibrationBenefit",
+            "saturationBenefit",
+            "evaluationBenefit",
+            "miniExerciseBenefit",
+            "trainingBenefit",
+            "vibrationLevelPref",
+            "vibrationIs",
+            "reductionWhileSitting",
+            "occuredBugs",
+            "buyRectify",
+            "rectifyPrice",
+        ]
+    }
+
+    fn to_numeric_vec(&self) -> Vec<Option<f64>> {
+        vec![
+            choice(&self.shirtComfort),
+            choice(&self.sensorIsMoving),
+            choice(&self.shirtWearDuration),
+            choice(&self.shirtWearWeekly),
+            choice(&self.rectifyDuration),
+            choice(&self.appUsability),
+            choice(&self.rectifyBenefit),
+            self.vibrationBenefit.as_f64(),
+            self.saturationBenefit.as_f64(),
+            self.evaluationBenefit.as_f64(),
+            self.miniExerciseBenefit.as_f64(),
+            self.trainingBenefit.as_f64(),
+            self.vibrationLevelPref.as_f64(),
+            choice(&self.vibrationIs),
+            choice(&self.reductionWhileSitting),
+            choice(&self.occuredBugs),
+            choice(&self.buyRectify),
+            self.rectifyPrice.as_f64(),
+        ]
+    }
+}
+
+impl NumericFeedback for BackpainFeedback {
+    fn numeric_columns() -> Vec<&'static str> {
+        vec![
+            "gender",
+            "age",
+            "weight",
+            "bodyHeight",
+            "backpainFrequency",
+            "backpainLevel",
+            "walkingPainLevel",
+            "painProblems",
+            "postureSelf",
+            "mobilifySelf",
+            "movementAtWork",
+            "movementFreeTime",
+            "standingDesk",
+            "sittingStandingSwitch",
+        ]
+    }
+
+    fn to_numeric_vec(&self) -> Vec<Option<f64>> {
+        vec![
+            choice(&self.gender),
+            self.age.as_f64(),
+            self.weight.as_f64(),
+            self.bodyHeight.as_f64(),
+            choice(&self.backpainFrequency),
+            self.backpainLevel.as_f64(),
+            self.walkingPainLevel.as_f64(),
+            choice(&self.painProblems),
+            self.postureSelf.as_f64(),
+            self.mobilifySelf.as_f64(),
+            self.movementAtWork.as_f64(),
+            self.movementFreeTime.as_f64(),
+            choice(&self.standingDesk),
+            choice(&self.sittingStandingSwitch),
+        ]
+    }
+}
+
+/// keeps only the first submission of identical answers, `feedback` should be sorted by time
+pub fn dedup_feedback<T: FeedbackContent>(feedback: Vec<TimedData<T>>) -> Vec<TimedData<T>> {
+    let mut seen = HashSet::new();
+    feedback
+        .into_iter()
+        .filter(|x| seen.insert(x.data.content_hash()))
+        .collect()
+}
+
+impl BackpainFeedback {
+    /// parses the json directly from a file or stream
+    pub fn from_reader(reader: impl Read) -> serde_json::Result<Self> {
+        serde_json::from_reader::<_, BackpainFeedback>(reader)
+    }
+}
+
 impl FromStr for BackpainFeedback {
     type Err = FeedbackParseError;
 
@@ -460,13 +636,21 @@ enum LeftRightRange {
     Na,
 }
 
+/// Valid values are `-2..=2` from only left to only right. Floats are rounded to the
+/// nearest integer first (`-0.0` is the center), anything else is `Na`.
 impl From<Number> for LeftRightRange {
     fn from(value: Number) -> Self {
-        match value.as_i64() {
+        let value = value.as_i64().or_else(|| {
+            value
+                .as_f64()
+                .filter(|x| x.is_finite())
+                .map(|x| x.round() as i64)
+        });
+        match value {
             Some(v) => match v {
                 -2 => LeftRightRange::OnlyLeft,
                 -1 => LeftRightRange::MostlyLeft,
-                -0 => LeftRightRange::Center,
+                0 => LeftRightRange::Center,
                 1 => LeftRightRange::MostlyRight,
                 2 => LeftRightRange::OnlyRight,
                 _ => LeftRightRange::Na,
@@ -716,3 +900,106 @@ pub fn gen_csv_line(rectify: RectifyFeedback, backpain: BackpainFeedback) -> Str
         backpain.sittingStandingSwitch,
     ).replace("\n", " | ").replace("\t", " ").replace(r#"\""#, "")
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{io::Cursor, str::FromStr};
+
+    use chrono::NaiveDate;
+    use timespan::TimedData;
+
+    use serde_json::Number;
+
+    use super::{
+        dedup_feedback, BackpainFeedback, FeedbackContent, LeftRightRange, RectifyFeedback,
+    };
+
+    pub(crate) fn backpain_json(backpain_level: &str) -> String {
+        format!(
+            r#"{{
+                "gender": "female", "age": 5, "weight": 7, "bodyHeight": 17,
+                "backpainFrequency": "seldom",
+                "ifBackpainWhere": {{"lumbal": true, "cervical": false}},
+                "ifBackpainWhereLR": {{"lumbal": 0}},
+                "backpainLevel": {},
+                "walkingPain": {{"no": true}}, "walkingPainLevel": 0,
+                "painProblems": "some",
+                "postureSelf": 3, "mobilifySelf": 4, "movementAtWork": 2, "movementFreeTime": 3,
+                "standingDesk": "no", "sittingStandingSwitch": "never",
+                "heavyObject": "yes", "highObject": "yes", "knowAboutFitForWork": "no",
+                "longStanding": "difficult", "lowObject": "yes",
+                "motivatedForFitWork": null, "takePartInFitForWork": null
+            }}"#,
+            backpain_level
+        )
+    }
+
+    #[test]
+    fn left_right_range_from_number() {
+        let parse = |s: &str| LeftRightRange::from(serde_json::from_str::<Number>(s).unwrap());
+
+        assert_eq!(parse("0"), LeftRightRange::Center);
+        assert_eq!(parse("-0.0"), LeftRightRange::Center);
+        assert_eq!(parse("2.0"), LeftRightRange::OnlyRight);
+        assert_eq!(parse("-1.2"), LeftRightRange::MostlyLeft);
+        assert_eq!(parse("3"), LeftRightRange::Na);
+    }
+
+    #[test]
+    fn backpain_from_reader() {
+        let json = backpain_json("3");
+
+        let from_reader = BackpainFeedback::from_reader(Cursor::new(json.as_bytes())).unwrap();
+
+        assert_eq!(from_reader, BackpainFeedback::from_str(&json).unwrap());
+        assert!(BackpainFeedback::from_reader(Cursor::new(b"{")).is_err());
+    }
+
+    #[test]
+    fn rectify_from_reader() {
+        let json = r#"{
+            "shirtComfort": "comfy", "sensorIsMoving": "good",
+            "shirtWearLocations": {"work": true, "home": false},
+            "shirtWearDuration": "foureight", "shirtWearWeekly": "seven",
+            "rectifyDuration": "eight",
+            "appUsability": "intelligible", "rectifyBenefit": "useful",
+            "vibrationBenefit": 3, "saturationBenefit": 2, "evaluationBenefit": 4,
+            "miniExerciseBenefit": 1, "trainingBenefit": 2, "otherFeatureWishes": null,
+            "vibrationLevelPref": 2, "vibrationMissingWhen": null, "vibrationIs": "weak",
+            "otherWishes": "none",
+            "reductionWhileSitting": "good", "increaseWhileMoving": "ok",
+            "occuredBugs": "some", "buyRectify": "yes",
+            "rectifyPrice": 100, "rectifyPricespan": null, "eMail": "a@b.c"
+        }"#;
+
+        let feedback = RectifyFeedback::from_reader(Cursor::new(json.as_bytes())).unwrap();
+
+        assert_eq!(feedback.otherWishes.as_deref(), Some("none"));
+        assert_eq!(feedback.eMail.as_deref(), Some("a@b.c"));
+        assert!(RectifyFeedback::from_reader(Cursor::new(b"{")).is_err());
+    }
+
+    #[test]
+    fn dedup_identical_feedback() {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let feedback = ["3", "3.0", "4"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, level)| TimedData {
+                time: day.and_hms_opt(10, i as u32, 0).unwrap(),
+                data: BackpainFeedback::from_str(&backpain_json(level)).unwrap(),
+            })
+            .collect::<Vec<TimedData<BackpainFeedback>>>();
+
+        assert_eq!(feedback[0].data, feedback[1].data);
+        assert_eq!(
+            feedback[0].data.content_hash(),
+            feedback[1].data.content_hash()
+        );
+        assert_ne!(feedback[0].data, feedback[2].data);
+
+        let deduped = dedup_feedback(feedback);
+        assert_eq!(deduped.len(), 2);
+        assert_eq!(deduped[0].time, day.and_hms_opt(10, 0, 0).unwrap());
+    }
+}
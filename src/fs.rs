@@ -1,16 +1,19 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use polars::export::regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, DirEntry, File};
+use std::hash::Hasher;
 use std::path::PathBuf;
 use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::misc::parse_dart_timestring;
 use crate::schema::OutputType;
+use crate::utils::fnv::Fnv1a;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+/// ordered by major, minor, patch and then build
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct AppVersion(usize, usize, usize, usize);
 
 #[derive(Debug, PartialEq, Eq)]
@@ -48,8 +51,51 @@ pub struct PhoneModel {
     pub model: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Platform {
+    IOS,
+    Android,
+    Unknown,
+}
+
+impl PhoneModel {
+    pub fn platform(&self) -> Platform {
+        self.platform_with(&HashMap::new())
+    }
+
+    /// `overrides` maps lowercase brand names to a platform and takes precedence
+    /// over the default inference
+    pub fn platform_with(&self, overrides: &HashMap<String, Platform>) -> Platform {
+        let brand = self.brand.trim().to_lowercase();
+        if let Some(platform) = overrides.get(&brand) {
+            return *platform;
+        }
+        match brand.as_str() {
+            "" | "unknown" | "na" => Platform::Unknown,
+            "apple" | "iphone" | "ipad" => Platform::IOS,
+            _ => Platform::Android,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
-struct ParsePhoneModelError;
+pub struct ParsePhoneModelError;
+
+impl FromStr for PhoneModel {
+    type Err = ParsePhoneModelError;
+
+    /// parses `"brand_model"` or `"brand model"`, everything after the first
+    /// separator belongs to the model
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(|c| c == '_' || c == ' ') {
+            Some((brand, model)) if !brand.is_empty() && !model.is_empty() => Ok(PhoneModel {
+                brand: brand.to_string(),
+                model: model.to_string(),
+            }),
+            _ => Err(ParsePhoneModelError),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct ParsedDir {
@@ -127,6 +173,44 @@ impl FromStr for ParsedDir {
     }
 }
 
+impl ParsedDir {
+    /// Hash over the relative paths and sizes of all files below the dir. It changes
+    /// when files are added, removed or change their size. The value is stable across
+    /// builds, every file adds its `/` separated path, a 0 byte and its little endian size.
+    pub fn content_fingerprint(&self) -> u64 {
+        let mut dirs = vec![self.path.clone()];
+        dirs.append(&mut traverse_dirs(&self.path).to_path_buf());
+
+        let mut files: Vec<(PathBuf, u64)> = dirs
+            .into_iter()
+            .map(|x| list_files(x))
+            .flatten()
+            .map(|x| {
+                (
+                    x.path()
+                        .strip_prefix(&self.path)
+                        .map(|x| x.to_path_buf())
+                        .unwrap_or(x.path()),
+                    x.metadata().map(|x| x.len()).unwrap_or(0),
+                )
+            })
+            .collect();
+        files.sort();
+
+        let mut hasher = Fnv1a::default();
+        for (path, size) in files {
+            let components = path
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>();
+            hasher.write(components.join("/").as_bytes());
+            hasher.write_u8(0);
+            hasher.write(&size.to_le_bytes());
+        }
+        hasher.finish()
+    }
+}
+
 impl TryFrom<DirEntry> for ParsedDir {
     type Error = ParseFlexDataDirNameError;
 
@@ -235,6 +319,8 @@ fn traverse_dirs(path: &PathBuf) -> Vec<fs::DirEntry> {
     return dirs;
 }
 
+/// `Uuid` compares the parsed bytes, so the case and the hyphenated or simple
+/// form of the uuid in the dir name don't matter
 pub fn find_uuid_dirs<'a>(paths: &Vec<ParsedDir>, uuid: &Uuid) -> HashSet<ParsedDir> {
     HashSet::from_iter(
         paths
@@ -244,6 +330,34 @@ pub fn find_uuid_dirs<'a>(paths: &Vec<ParsedDir>, uuid: &Uuid) -> HashSet<Parsed
     )
 }
 
+/// like [`find_uuid_dirs`] but in a deterministic order, see [`sort_dirs`]
+pub fn find_uuid_dirs_sorted(paths: &Vec<ParsedDir>, uuid: &Uuid) -> Vec<ParsedDir> {
+    sort_dirs(find_uuid_dirs(paths, uuid))
+}
+
+/// oldest `initial_app_start` first, ties are ordered by the path
+pub fn sort_dirs<I: IntoIterator<Item = ParsedDir>>(dirs: I) -> Vec<ParsedDir> {
+    let mut dirs = dirs.into_iter().collect::<Vec<ParsedDir>>();
+    dirs.sort_by(|a, b| {
+        a.initial_app_start
+            .cmp(&b.initial_app_start)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    dirs
+}
+
+/// dirs written by an app version in `[min, max)`
+pub fn filter_by_version_range(
+    dirs: &Vec<ParsedDir>,
+    min: AppVersion,
+    max: AppVersion,
+) -> Vec<ParsedDir> {
+    dirs.iter()
+        .filter(|x| min <= x.app_version && x.app_version < max)
+        .cloned()
+        .collect()
+}
+
 pub fn find_uuids(paths: &Vec<ParsedDir>) -> HashSet<Uuid> {
     HashSet::from_iter(paths.iter().map(|x| x.uuid))
 }
@@ -267,6 +381,11 @@ pub fn find_sensors(user_dirs: &Vec<PathBuf>) -> HashSet<String> {
     find_sensor_names(get_subdirs(user_dirs, OutputType::logs))
 }
 
+/// every sensor seen by any user under `path`
+pub fn all_sensors(path: &PathBuf) -> HashSet<String> {
+    find_sensors(&parse_subdirs(path).to_paths())
+}
+
 pub fn get_subdirs(user_dirs: &Vec<PathBuf>, subdir_type: OutputType) -> Vec<DirEntry> {
     user_dirs
         .into_iter()
@@ -298,34 +417,30 @@ pub fn find_sensor_names(files: Vec<DirEntry>) -> HashSet<String> {
     let mut set: HashSet<String> = HashSet::new();
     for entry in files.into_iter() {
         if let Ok(content) = fs::read_to_string(entry.path()) {
-            if let Some(matches) = sensor_regex.captures(content.as_str()) {
-                if let Some(first) = matches.get(0) {
-                    set.insert(first.as_str().to_string());
-                    //return set;
-                }
-            }
+            set.extend(
+                sensor_regex
+                    .find_iter(&content)
+                    .map(|x| x.as_str().to_string()),
+            );
         }
     }
     set
 }
 
-pub fn concat_csv_files(paths: &Vec<PathBuf>) -> PathBuf {
+/// appends `paths` into a new temp file, the temp file is removed again if one of the files
+/// can't be read
+pub fn concat_csv_files(paths: &Vec<PathBuf>) -> std::io::Result<PathBuf> {
     let mut temp_dir = std::env::temp_dir();
     let uuid = Uuid::new_v4().to_string();
     temp_dir.push(uuid);
-    File::create(temp_dir.clone()).expect("could not create file");
-    let mut file = fs::OpenOptions::new()
-        .append(true)
-        .open(temp_dir.clone())
-        .expect("could not open temp file");
+    let mut file = File::create(&temp_dir)?;
     for path in paths {
-        let mut f2 = fs::OpenOptions::new().read(true).open(path).unwrap();
-        match std::io::copy(&mut f2, &mut file) {
-            Ok(_) => {}
-            Err(_) => {}
+        if let Err(e) = File::open(path).and_then(|mut f| std::io::copy(&mut f, &mut file)) {
+            let _ = fs::remove_file(&temp_dir);
+            return Err(e);
         }
     }
-    temp_dir
+    Ok(temp_dir)
 }
 
 fn path_to_begin_timestamp(f: &PathBuf) -> String {
@@ -383,3 +498,181 @@ pub fn filter_files_by_date(files: &Vec<PathBuf>, date: NaiveDate) -> Vec<PathBu
         .map(|x| x.to_owned())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use uuid::Uuid;
+
+    use super::{
+        all_sensors, concat_csv_files, filter_by_version_range, find_uuid_dirs,
+        find_uuid_dirs_sorted, AppVersion, ParsePhoneModelError, ParsedDir, PhoneModel, Platform,
+    };
+
+    fn phone(brand: &str) -> PhoneModel {
+        PhoneModel {
+            brand: brand.into(),
+            model: "model".into(),
+        }
+    }
+
+    #[test]
+    fn platform_from_brand() {
+        assert_eq!(phone("Apple").platform(), Platform::IOS);
+        assert_eq!(phone("Samsung").platform(), Platform::Android);
+        assert_eq!(phone("").platform(), Platform::Unknown);
+        assert_eq!(
+            phone("Huawei").platform_with(&HashMap::from([("huawei".into(), Platform::Unknown)])),
+            Platform::Unknown
+        );
+    }
+
+    #[test]
+    fn parse_phone_model() {
+        assert_eq!(
+            PhoneModel::from_str("Apple iPhone12"),
+            Ok(PhoneModel {
+                brand: "Apple".into(),
+                model: "iPhone12".into()
+            })
+        );
+        assert_eq!(
+            PhoneModel::from_str("samsung_SM_G991B"),
+            Ok(PhoneModel {
+                brand: "samsung".into(),
+                model: "SM_G991B".into()
+            })
+        );
+        assert_eq!(PhoneModel::from_str("samsung"), Err(ParsePhoneModelError));
+    }
+
+    #[test]
+    fn find_uuid_dirs_ignores_case_and_form() {
+        let dirs = vec![
+            ParsedDir::from_str(
+                "2023-04-12_10_20_30_Apple_iPhone12_1.2.3-45_6f9619ff8b86d011b42d00c04fc964ff",
+            )
+            .unwrap(),
+            ParsedDir::from_str(
+                "2023-04-13_10_20_30_Apple_iPhone12_1.2.3-45_6f9619ff-8b86-d011-b42d-00c04fc964ff",
+            )
+            .unwrap(),
+        ];
+        let uuid = Uuid::parse_str("6F9619FF-8B86-D011-B42D-00C04FC964FF").unwrap();
+
+        assert_eq!(find_uuid_dirs(&dirs, &uuid).len(), 2);
+    }
+
+    #[test]
+    fn sorted_uuid_dirs_by_app_start() {
+        let uuid = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+        let dir = |date: &str| {
+            ParsedDir::from_str(&format!(
+                "{}_10_20_30_Apple_iPhone12_1.2.3-45_{}",
+                date, uuid
+            ))
+            .unwrap()
+        };
+        let dates = ["2023-04-14", "2023-04-12", "2023-04-13", "2023-03-01"];
+        let dirs = dates.iter().map(|x| dir(x)).collect::<Vec<ParsedDir>>();
+        let uuid = Uuid::parse_str(uuid).unwrap();
+
+        let sorted = find_uuid_dirs_sorted(&dirs, &uuid);
+        let mut reversed = dirs.clone();
+        reversed.reverse();
+
+        assert_eq!(
+            sorted
+                .iter()
+                .map(|x| x.initial_app_start.date().to_string())
+                .collect::<Vec<String>>(),
+            vec!["2023-03-01", "2023-04-12", "2023-04-13", "2023-04-14"]
+        );
+        assert_eq!(find_uuid_dirs_sorted(&reversed, &uuid), sorted);
+    }
+
+    #[test]
+    fn version_range() {
+        let dir = |version: &str| {
+            ParsedDir::from_str(&format!(
+                "2023-04-12_10_20_30_Apple_iPhone12_{}_6f9619ff-8b86-d011-b42d-00c04fc964ff",
+                version
+            ))
+            .unwrap()
+        };
+        let dirs = vec![dir("1.2.3-45"), dir("1.2.4-46"), dir("1.10.0-50")];
+
+        let filtered =
+            filter_by_version_range(&dirs, AppVersion(1, 2, 4, 0), AppVersion(1, 10, 0, 50));
+
+        assert_eq!(filtered, vec![dirs[1].clone()]);
+        assert!(AppVersion(1, 10, 0, 0) > AppVersion(1, 9, 9, 99));
+    }
+
+    #[test]
+    fn sensors_of_all_users() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        for (uuid, log) in [
+            (Uuid::new_v4(), "connected to FT1A2\nconnected to FT0FF"),
+            (Uuid::new_v4(), "connected to FT1A2, later to FTB33"),
+        ] {
+            let logs = root
+                .join(format!(
+                    "2023-04-12_10_20_30_Apple_iPhone12_1.2.3-45_{}",
+                    uuid
+                ))
+                .join("logs");
+            std::fs::create_dir_all(&logs).unwrap();
+            std::fs::write(logs.join("log.txt"), log).unwrap();
+        }
+
+        let sensors = all_sensors(&root);
+        std::fs::remove_dir_all(root).unwrap();
+
+        let mut sensors = sensors.into_iter().collect::<Vec<String>>();
+        sensors.sort();
+        assert_eq!(sensors, vec!["FT0FF", "FT1A2", "FTB33"]);
+    }
+
+    #[test]
+    fn fingerprint_changes_with_content() {
+        let mut path = std::env::temp_dir();
+        path.push(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(path.join("points")).unwrap();
+        std::fs::write(path.join("points").join("a"), "1,2,3").unwrap();
+
+        let dir = ParsedDir {
+            path: path.clone(),
+            uuid: Uuid::new_v4(),
+            initial_app_start: Default::default(),
+            phone: PhoneModel::from_str("Apple iPhone12").unwrap(),
+            app_version: AppVersion(1, 2, 3, 45),
+        };
+        let before = dir.content_fingerprint();
+        assert_eq!(before, dir.content_fingerprint());
+
+        std::fs::write(path.join("points").join("b"), "4,5,6").unwrap();
+        let after = dir.content_fingerprint();
+        std::fs::remove_dir_all(path).unwrap();
+
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn concat_unreadable_file_is_io_error() {
+        let mut readable = std::env::temp_dir();
+        readable.push(Uuid::new_v4().to_string());
+        std::fs::write(&readable, "1,2,3\n").unwrap();
+        let missing = std::env::temp_dir().join(Uuid::new_v4().to_string());
+
+        let concatenated = concat_csv_files(&vec![readable.clone()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&concatenated).unwrap(), "1,2,3\n");
+        let err = concat_csv_files(&vec![readable.clone(), missing]).unwrap_err();
+        std::fs::remove_file(readable).unwrap();
+        std::fs::remove_file(concatenated).unwrap();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod fs;
 pub mod df;
+pub mod error;
 pub mod misc;
 pub mod schema;
 pub mod evaluation;
@@ -11,3 +12,5 @@ pub mod vec_vec_utils;
 pub mod utils;
 pub mod clustered_data;
 mod grep_wrapper;
+
+pub use timespan::{DatedData, TimedData, Timespan};
@@ -1,8 +1,10 @@
-use crate::{fs::get_subdirs, misc::parse_dart_timestring, schema::OutputType};
+use crate::{df::logs::LogsDf, fs::get_subdirs, misc::parse_dart_timestring, schema::OutputType};
 use chrono::NaiveDateTime;
+use polars::prelude::PolarsResult;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr, sync::OnceLock};
+use timespan::{TimedData, Timespan};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -12,7 +14,10 @@ pub struct LogEntry {
     pub message: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Levels of the dart `logging` package. The ordering follows the severity,
+/// so `FINEST < FINER < ... < SEVERE < SHOUT < OFF`, i.e. `level >= LogLevel::WARNING`
+/// selects warnings and everything more severe.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LogLevel {
     OFF,
     SHOUT,
@@ -25,6 +30,35 @@ pub enum LogLevel {
     FINEST,
 }
 
+impl LogLevel {
+    /// numeric value of the level as defined by the dart `logging` package
+    pub fn value(&self) -> u16 {
+        match self {
+            LogLevel::FINEST => 300,
+            LogLevel::FINER => 400,
+            LogLevel::FINE => 500,
+            LogLevel::CONFIG => 700,
+            LogLevel::INFO => 800,
+            LogLevel::WARNING => 900,
+            LogLevel::SEVERE => 1000,
+            LogLevel::SHOUT => 1200,
+            LogLevel::OFF => 2000,
+        }
+    }
+}
+
+impl PartialOrd for LogLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogLevel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.value().cmp(&other.value())
+    }
+}
+
 impl FromStr for LogLevel {
     type Err = ParseLogEntryError;
 
@@ -32,7 +66,7 @@ impl FromStr for LogLevel {
         Ok(match s.trim() {
             "OFF" => LogLevel::OFF,
             "SHOUT" => LogLevel::SHOUT,
-            "SEVER" => LogLevel::SEVERE,
+            "SEVERE" | "SEVER" => LogLevel::SEVERE,
             "WARNING" => LogLevel::WARNING,
             "INFO" => LogLevel::INFO,
             "CONFIG" => LogLevel::CONFIG,
@@ -50,6 +84,8 @@ pub enum ExerciseLength {
     M,
     L,
     XL,
+    /// the added saturation couldn't be parsed from the message
+    Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +115,153 @@ impl LogEvents {
         })
         .unwrap()
     }
+
+    /// the event of the same kind as `self` with the payload parsed from `entry`.
+    /// The exercise length follows the added saturation (1: S, 2: M, 3: L, more: XL),
+    /// a message without a saturation gives `ExerciseLength::Unknown`.
+    fn with_payload(&self, entry: &LogEntry) -> LogEvents {
+        static SATURATION: OnceLock<Regex> = OnceLock::new();
+        match self {
+            LogEvents::Exercise(_) => LogEvents::Exercise(
+                match SATURATION
+                    .get_or_init(|| Regex::new(r"saturation added: (\d+)").unwrap())
+                    .captures(&entry.message)
+                    // only digits, so the parse can only fail on an overflow
+                    .map(|x| x[1].parse::<u32>().unwrap_or(u32::MAX))
+                {
+                    Some(1) => ExerciseLength::S,
+                    Some(2) => ExerciseLength::M,
+                    Some(3) => ExerciseLength::L,
+                    Some(4..) => ExerciseLength::XL,
+                    _ => ExerciseLength::Unknown,
+                },
+            ),
+            LogEvents::FBGB(_) => {
+                let message = entry.message.to_lowercase();
+                LogEvents::FBGB(if message.contains("foreground") {
+                    FGBGMode::Foreground
+                } else if message.contains("background") {
+                    FGBGMode::Background
+                } else {
+                    FGBGMode::Switch
+                })
+            }
+            event => event.clone(),
+        }
+    }
+}
+
+/// An entry of at least `min_level` counts as a crash if nothing was logged for
+/// `silence_ms` afterwards. Without `silence_ms` every such entry counts.
+#[derive(Debug, Clone)]
+pub struct CrashPattern {
+    pub min_level: LogLevel,
+    pub silence_ms: Option<i64>,
+}
+
+impl Default for CrashPattern {
+    fn default() -> Self {
+        Self {
+            min_level: LogLevel::SEVERE,
+            silence_ms: Some(60_000),
+        }
+    }
+}
+
+/// `entries` have to be sorted by timestamp, the last entry is followed by silence
+pub fn find_probable_crashes(entries: &[LogEntry], pattern: &CrashPattern) -> Vec<LogEntry> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(i, x)| {
+            x.log_level >= pattern.min_level
+                && match (pattern.silence_ms, entries.get(i + 1)) {
+                    (Some(silence), Some(next)) => {
+                        (next.timestamp - x.timestamp).num_milliseconds() >= silence
+                    }
+                    _ => true,
+                }
+        })
+        .map(|x| x.1.clone())
+        .collect()
+}
+
+/// Subsystem a log entry belongs to, derived from its logger name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LogCategory {
+    Bluetooth,
+    Vibration,
+    Sync,
+    Exercise,
+    Other,
+}
+
+/// Maps logger names to a [`LogCategory`], unknown loggers are `LogCategory::Other`.
+#[derive(Debug, Clone)]
+pub struct LoggerCategories(pub HashMap<String, LogCategory>);
+
+impl LoggerCategories {
+    pub fn with(mut self, logger: &str, category: LogCategory) -> Self {
+        self.0.insert(logger.to_string(), category);
+        self
+    }
+
+    pub fn category(&self, logger: &str) -> LogCategory {
+        self.0.get(logger).copied().unwrap_or(LogCategory::Other)
+    }
+}
+
+impl Default for LoggerCategories {
+    /// the loggers of the app
+    fn default() -> Self {
+        LoggerCategories(
+            [
+                ("BLE", LogCategory::Bluetooth),
+                ("Ble", LogCategory::Bluetooth),
+                ("Bluetooth", LogCategory::Bluetooth),
+                ("VibrationTrigger", LogCategory::Vibration),
+                ("Vibration", LogCategory::Vibration),
+                ("Sync", LogCategory::Sync),
+                ("Upload", LogCategory::Sync),
+                ("Exercise", LogCategory::Exercise),
+            ]
+            .into_iter()
+            .map(|(logger, category)| (logger.to_string(), category))
+            .collect(),
+        )
+    }
+}
+
+impl LogEntry {
+    pub fn category(&self) -> LogCategory {
+        self.category_with(&LoggerCategories::default())
+    }
+
+    pub fn category_with(&self, categories: &LoggerCategories) -> LogCategory {
+        categories.category(&self.logger)
+    }
+
+    /// new level of a `put: HiveKey.vibrationTriggerLevel` entry
+    pub fn vibration_level(&self) -> Option<i64> {
+        static LEVEL: OnceLock<Regex> = OnceLock::new();
+        LEVEL
+            .get_or_init(|| Regex::new(r"HiveKey\.vibrationTriggerLevel\D*?(-?\d+)").unwrap())
+            .captures(&self.message)
+            .and_then(|x| x[1].parse().ok())
+    }
+}
+
+/// changes of the vibration trigger level in the order of `entries`
+pub fn vibration_level_changes(entries: &[LogEntry]) -> Vec<TimedData<i64>> {
+    entries
+        .iter()
+        .filter_map(|x| {
+            x.vibration_level().map(|level| TimedData {
+                time: x.timestamp,
+                data: level,
+            })
+        })
+        .collect()
 }
 
 pub struct Logs(Vec<PathBuf>);
@@ -97,6 +280,82 @@ impl Logs {
             })
     }
 
+    /// all parsable entries sorted by their timestamp
+    pub fn entries(&self) -> Vec<LogEntry> {
+        let mut m: Vec<LogEntry> = vec![];
+        self.iter_lines(|line| {
+            if let Ok(entry) = LogEntry::from_str(line) {
+                m.push(entry);
+            }
+        });
+        m.sort_by_key(|x| x.timestamp);
+        m
+    }
+
+    /// `SEVERE` or `SHOUT` entries followed by at least `window_ms` without logging
+    pub fn probable_crashes(&self, window_ms: i64) -> Vec<LogEntry> {
+        self.probable_crashes_with(&CrashPattern {
+            silence_ms: Some(window_ms),
+            ..Default::default()
+        })
+    }
+
+    pub fn probable_crashes_with(&self, pattern: &CrashPattern) -> Vec<LogEntry> {
+        find_probable_crashes(&self.entries(), pattern)
+    }
+
+    pub fn vibration_level_changes(&self) -> Vec<TimedData<i64>> {
+        let mut entries = self.filter(LogEvents::VibrationLevelChange.query());
+        entries.sort_by_key(|x| x.timestamp);
+        vibration_level_changes(&entries)
+    }
+
+    /// all lines matching one of `events` (the first that matches wins) in
+    /// chronological order, payloads are parsed from the message
+    pub fn timeline(&self, events: &[LogEvents]) -> Vec<TimedData<LogEvents>> {
+        let queries = events
+            .iter()
+            .map(|x| (x, x.query()))
+            .collect::<Vec<(&LogEvents, Regex)>>();
+        let mut m: Vec<TimedData<LogEvents>> = vec![];
+        self.iter_lines(|line| {
+            if let Some((event, _)) = queries.iter().find(|(_, query)| query.is_match(line)) {
+                if let Ok(entry) = LogEntry::from_str(line) {
+                    m.push(TimedData {
+                        time: entry.timestamp,
+                        data: event.with_payload(&entry),
+                    });
+                }
+            }
+        });
+        m.sort_by_key(|x| x.time);
+        m
+    }
+
+    /// Entries of at least `min_level`, inside of `ts` and with a line matching `pattern`
+    /// (each if given) as a frame, sorted by time.
+    pub fn query(
+        &self,
+        min_level: LogLevel,
+        ts: Option<Timespan>,
+        pattern: Option<Regex>,
+    ) -> PolarsResult<LogsDf> {
+        let mut m: Vec<LogEntry> = vec![];
+        self.iter_lines(|line| {
+            if pattern.as_ref().map_or(true, |x| x.is_match(line)) {
+                if let Ok(entry) = LogEntry::from_str(line) {
+                    if entry.log_level >= min_level
+                        && ts.as_ref().map_or(true, |x| x.is_inside(entry.timestamp))
+                    {
+                        m.push(entry);
+                    }
+                }
+            }
+        });
+        m.sort_by_key(|x| x.timestamp);
+        LogsDf::from_entries(&m)
+    }
+
     pub fn filter(&self, regex: Regex) -> Vec<LogEntry> {
         let mut m: Vec<LogEntry> = vec![];
         self.iter_lines(|line| {
@@ -168,3 +427,222 @@ pub fn find_in_logs(dirs: &Vec<PathBuf>, regex: Regex) -> Vec<LogEntry> {
     }
     m
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use regex::Regex;
+    use timespan::Timespan;
+    use uuid::Uuid;
+
+    use super::{
+        find_probable_crashes, vibration_level_changes, CrashPattern, ExerciseLength, LogCategory,
+        LogEntry, LogEvents, LogLevel, LoggerCategories, Logs,
+    };
+    use crate::schema::OutputType;
+
+    #[test]
+    fn severe_entry_followed_by_silence() {
+        let entries = [
+            "2023-04-12 10:20:30.000, Ble, SEVERE, connection failed",
+            "2023-04-12 10:20:40.000, Ble, INFO, reconnected",
+            "2023-04-12 10:21:00.000, App, SEVERE, unhandled exception",
+            "2023-04-12 10:45:00.000, App, INFO, app start",
+        ]
+        .into_iter()
+        .map(|x| LogEntry::from_str(x).unwrap())
+        .collect::<Vec<LogEntry>>();
+
+        let crashes = find_probable_crashes(&entries, &CrashPattern::default());
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].message, "unhandled exception");
+
+        let pattern = CrashPattern {
+            silence_ms: None,
+            ..Default::default()
+        };
+        assert_eq!(find_probable_crashes(&entries, &pattern).len(), 2);
+    }
+
+    #[test]
+    fn timeline_in_time_order() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut dir = root.clone();
+        dir.push(OutputType::logs.subdir());
+        fs::create_dir_all(&dir).unwrap();
+        dir.push("log.txt");
+        fs::write(
+            &dir,
+            [
+                "2023-04-12 10:30:00.000, Exercise, INFO, saturation added: 2",
+                "2023-04-12 10:20:00.000, VibrationTrigger, INFO, vibration: 1",
+                "2023-04-12 10:25:00.000, App, INFO, unrelated",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let timeline = Logs::new(vec![root.clone()])
+            .timeline(&[LogEvents::Vibration, LogEvents::Exercise(ExerciseLength::S)]);
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(timeline.len(), 2);
+        assert!(matches!(timeline[0].data, LogEvents::Vibration));
+        assert!(matches!(
+            timeline[1].data,
+            LogEvents::Exercise(ExerciseLength::M)
+        ));
+        assert!(timeline[0].time < timeline[1].time);
+    }
+
+    #[test]
+    fn exercise_length_from_saturation() {
+        let length = |message: &str| {
+            let entry = LogEntry::from_str(&format!(
+                "2023-04-12 10:30:00.000, Exercise, INFO, {}",
+                message
+            ))
+            .unwrap();
+            match LogEvents::Exercise(ExerciseLength::S).with_payload(&entry) {
+                LogEvents::Exercise(x) => x,
+                _ => panic!("expected an exercise"),
+            }
+        };
+
+        assert!(matches!(length("saturation added: 3"), ExerciseLength::L));
+        assert!(matches!(length("saturation added: 7"), ExerciseLength::XL));
+        assert!(matches!(
+            length("saturation added: 99999999999"),
+            ExerciseLength::XL
+        ));
+        assert!(matches!(
+            length("saturation added: lots"),
+            ExerciseLength::Unknown
+        ));
+    }
+
+    #[test]
+    fn query_by_level_time_and_pattern() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut dir = root.clone();
+        dir.push(OutputType::logs.subdir());
+        fs::create_dir_all(&dir).unwrap();
+        dir.push("log.txt");
+        fs::write(
+            &dir,
+            [
+                "2023-04-12 10:20:00.000, BLE, WARNING, disconnected from FT1A2",
+                "2023-04-12 10:25:00.000, BLE, INFO, connected to FT1A2",
+                "2023-04-12 10:30:00.000, App, SEVERE, unhandled exception",
+                "2023-04-12 11:30:00.000, BLE, SEVERE, disconnected from FT1A2",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        let logs = Logs::new(vec![root.clone()]);
+        let day = chrono::NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let ts = Timespan {
+            begin: day.and_hms_opt(10, 0, 0).unwrap(),
+            end: day.and_hms_opt(11, 0, 0).unwrap(),
+        };
+
+        let all = logs.query(LogLevel::FINEST, None, None).unwrap();
+        let df = logs
+            .query(
+                LogLevel::WARNING,
+                Some(ts),
+                Some(Regex::new("FT1A2").unwrap()),
+            )
+            .unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(all.height(), 4);
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df["message"].utf8().unwrap().get(0),
+            Some("disconnected from FT1A2")
+        );
+    }
+
+    #[test]
+    fn categorize_loggers() {
+        let entry = |logger: &str| {
+            LogEntry::from_str(&format!(
+                "2023-04-12 10:20:30.000, {}, INFO, message",
+                logger
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(entry("BLE").category(), LogCategory::Bluetooth);
+        assert_eq!(entry("VibrationTrigger").category(), LogCategory::Vibration);
+        assert_eq!(entry("Sync").category(), LogCategory::Sync);
+        assert_eq!(entry("Exercise").category(), LogCategory::Exercise);
+        assert_eq!(entry("HomeScreen").category(), LogCategory::Other);
+
+        let categories = LoggerCategories::default().with("HomeScreen", LogCategory::Sync);
+        assert_eq!(
+            entry("HomeScreen").category_with(&categories),
+            LogCategory::Sync
+        );
+    }
+
+    #[test]
+    fn parse_vibration_level() {
+        let entries = [
+            "2023-04-12 10:20:30.000, Hive, INFO, put: HiveKey.vibrationTriggerLevel: 7",
+            "2023-04-12 10:21:30.000, Hive, INFO, put: HiveKey.other: 3",
+        ]
+        .into_iter()
+        .map(|x| LogEntry::from_str(x).unwrap())
+        .collect::<Vec<LogEntry>>();
+
+        let changes = vibration_level_changes(&entries);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].data, 7);
+        assert_eq!(changes[0].time, entries[0].timestamp);
+    }
+
+    #[test]
+    fn truncated_sever_level_is_severe() {
+        // `SEVER` lines are classified like before, as `SEVERE`
+        for level in ["SEVER", "SEVERE"] {
+            let entry = LogEntry::from_str(&format!(
+                "2023-04-12 10:30:00.000, App, {}, unhandled exception",
+                level
+            ))
+            .unwrap();
+            assert_eq!(entry.log_level, LogLevel::SEVERE);
+        }
+    }
+
+    #[test]
+    fn log_level_severity_order() {
+        assert!(LogLevel::SEVERE > LogLevel::INFO);
+        assert!(LogLevel::FINEST < LogLevel::FINER);
+
+        let mut levels = vec![
+            LogLevel::INFO,
+            LogLevel::SHOUT,
+            LogLevel::FINE,
+            LogLevel::WARNING,
+            LogLevel::SEVERE,
+        ];
+        levels.sort();
+
+        assert_eq!(
+            levels,
+            vec![
+                LogLevel::FINE,
+                LogLevel::INFO,
+                LogLevel::WARNING,
+                LogLevel::SEVERE,
+                LogLevel::SHOUT,
+            ]
+        );
+    }
+}
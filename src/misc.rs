@@ -4,39 +4,99 @@ use std::{
     time::SystemTime,
 };
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime};
 use polars::prelude::DataFrame;
 
 use crate::schema::OutputType;
 
+pub const ISO_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+/// like [`ISO_TIME_FORMAT`] with an offset like `+02:00`
+pub const ISO_TIME_OFFSET_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%:z";
+
+/// `None` if the file can't be read or is empty
 pub fn read_first_line(path: &PathBuf) -> Option<String> {
-    let f = std::fs::File::open(path).unwrap();
+    let f = std::fs::File::open(path).ok()?;
     let mut buf = String::new();
     match BufReader::new(f).read_line(&mut buf) {
+        Ok(0) | Err(_) => None,
         Ok(_) => Some(buf),
-        Err(_) => None,
     }
 }
 
-pub fn read_first_n_chars(path: &PathBuf) -> String {
-    let mut buf: [u8; 4] = [0; 4];
-    match BufReader::new(std::fs::File::open(path).unwrap()).read_exact(&mut buf) {
-        _ => {}
+/// a header line starts with a column name instead of a number or timestamp
+pub fn is_csv_header(line: &str) -> bool {
+    is_csv_header_with(line, b',')
+}
+
+pub fn is_csv_header_with(line: &str, delimiter: u8) -> bool {
+    match line.split(delimiter as char).next() {
+        Some(field) => {
+            let field = field.trim();
+            !field.is_empty() && field.chars().all(|c| c.is_alphabetic() || c == '_')
+        }
+        None => false,
+    }
+}
+
+pub fn has_csv_header(path: &PathBuf) -> bool {
+    has_csv_header_with(path, b',')
+}
+
+pub fn has_csv_header_with(path: &PathBuf, delimiter: u8) -> bool {
+    match read_first_line(path) {
+        Some(line) => is_csv_header_with(&line, delimiter),
+        None => false,
+    }
+}
+
+/// first line of the file which is not a header
+pub fn read_first_data_line(path: &PathBuf) -> Option<String> {
+    read_first_data_line_with(path, b',')
+}
+
+pub fn read_first_data_line_with(path: &PathBuf, delimiter: u8) -> Option<String> {
+    let f = std::fs::File::open(path).ok()?;
+    BufReader::new(f)
+        .lines()
+        .take(2)
+        .filter_map(|x| x.ok())
+        .find(|x| !is_csv_header_with(x, delimiter))
+}
+
+/// the first (up to) 4 bytes of the file, `None` if it can't be read or is empty
+pub fn read_first_n_chars(path: &PathBuf) -> Option<String> {
+    let mut buf = vec![];
+    std::fs::File::open(path)
+        .ok()?
+        .take(4)
+        .read_to_end(&mut buf)
+        .ok()?;
+    match buf.is_empty() {
+        true => None,
+        false => Some(String::from_utf8_lossy(&buf).to_string()),
     }
-    (*String::from_utf8_lossy(&buf[..])).to_string()
 }
 
 pub fn get_number_of_csv_fields(path: &PathBuf) -> usize {
+    get_number_of_csv_fields_with(path, b',')
+}
+
+/// like [`get_number_of_csv_fields`] for files with another delimiter, e.g. `b';'`
+pub fn get_number_of_csv_fields_with(path: &PathBuf, delimiter: u8) -> usize {
     let f = std::fs::File::open(path).unwrap();
     let mut buf = String::new();
     BufReader::new(f)
         .read_line(&mut buf)
         .expect("could not read first line");
-    buf.splitn(100, ',').count()
+    buf.splitn(100, delimiter as char).count()
 }
 
 pub fn get_num_of_sensors_from_file(dir: &PathBuf) -> usize {
-    get_num_of_sensors(get_number_of_csv_fields(dir))
+    get_num_of_sensors_from_file_with(dir, b',')
+}
+
+pub fn get_num_of_sensors_from_file_with(dir: &PathBuf, delimiter: u8) -> usize {
+    get_num_of_sensors(get_number_of_csv_fields_with(dir, delimiter))
 }
 
 pub fn get_num_of_sensors(num_of_fields: usize) -> usize {
@@ -47,8 +107,37 @@ pub fn get_num_of_sensors(num_of_fields: usize) -> usize {
     }
 }
 
+/// Uses the column names like [`infer_df_type`] if the file has a header. Headerless
+/// new schema raw files are recognized by their serialized list columns (`[..]`),
+/// everything else by the number of fields.
 pub fn infer_file_type(path: &PathBuf) -> OutputType {
-    let n = get_number_of_csv_fields(path);
+    infer_file_type_with(path, b',')
+}
+
+pub fn infer_file_type_with(path: &PathBuf, delimiter: u8) -> OutputType {
+    if let Some(line) = read_first_line(path) {
+        if is_csv_header_with(&line, delimiter) {
+            let names: Vec<&str> = line
+                .trim()
+                .split(delimiter as char)
+                .map(|x| x.trim().trim_matches('"'))
+                .collect();
+            if names.contains(&"v") || names.contains(&"left") {
+                return OutputType::raw;
+            } else if names.contains(&"score") {
+                return OutputType::points;
+            }
+        } else if split_csv_line(&line, delimiter)
+            .iter()
+            .filter(|x| is_number_list(x))
+            .count()
+            >= 2
+        {
+            // new schema raw files store the left and right sensors as lists
+            return OutputType::raw;
+        }
+    }
+    let n = get_number_of_csv_fields_with(path, delimiter);
     match n {
         5 => OutputType::points,
         10.. => OutputType::raw,
@@ -56,6 +145,34 @@ pub fn infer_file_type(path: &PathBuf) -> OutputType {
     }
 }
 
+/// splits at `delimiter` outside of double quotes
+fn split_csv_line(line: &str, delimiter: u8) -> Vec<&str> {
+    let mut fields = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == delimiter as char && !quoted {
+            fields.push(&line[start..i]);
+            start = i + 1;
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// a whole field like `[1 2 3]` or `"[1,2,3]"`
+fn is_number_list(field: &str) -> bool {
+    let field = field.trim().trim_matches('"');
+    field.len() >= 2
+        && field.starts_with('[')
+        && field.ends_with(']')
+        && field[1..field.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_digit() || " ,.-".contains(c))
+}
+
 pub fn infer_df_type(df: &DataFrame) -> OutputType {
     let schema = df.schema();
     if schema.contains("v") {
@@ -87,3 +204,65 @@ pub fn parse_dart_timestring(s: &str) -> Result<NaiveDateTime, chrono::ParseErro
 pub fn parse_dart_timestring_short(s: &str) -> Result<NaiveDateTime, chrono::ParseError> {
     NaiveDateTime::parse_from_str(s.replace("_", ":").as_str(), "%Y-%m-%d %H:%M:%S.%f")
 }
+
+/// parses ISO 8601 / RFC 3339 timestamps, strings without an offset are taken as UTC
+pub fn parse_iso_timestring(s: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+    match DateTime::parse_from_rfc3339(s.trim()) {
+        Ok(t) => Ok(t.naive_utc()),
+        Err(_) => NaiveDateTime::parse_from_str(s.trim(), ISO_TIME_FORMAT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{infer_file_type, read_first_line, read_first_n_chars};
+    use crate::{
+        df::{tests::temp_file, TableFormat},
+        schema::OutputType,
+    };
+
+    #[test]
+    fn short_and_empty_files() {
+        let short = temp_file("ab", "");
+        let empty = temp_file("", "");
+
+        assert_eq!(read_first_n_chars(&short), Some("ab".to_string()));
+        assert_eq!(read_first_line(&short), Some("ab".to_string()));
+        assert_eq!(read_first_n_chars(&empty), None);
+        assert_eq!(read_first_line(&empty), None);
+        assert!(TableFormat::from_str(short.to_str().unwrap()).is_err());
+        assert!(TableFormat::from_str(empty.to_str().unwrap()).is_err());
+
+        for path in [short, empty] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(read_first_line(&"does/not/exist".into()), None);
+    }
+
+    #[test]
+    fn infer_new_schema_raw_file() {
+        let with_header = temp_file(
+            "t,left,right,v\n1681294830000,\"[1 2]\",\"[3 4]\",4000\n",
+            ".csv",
+        );
+        let without_header = temp_file("1681294830000,\"[1 2]\",\"[3 4]\",4000\n", ".csv");
+        let comma_lists = temp_file("1681294830000,\"[1,2]\",\"[3,4]\",4000\n", ".csv");
+        let points = temp_file("1681294830000,80.0,70.0,90.0,walking\n", ".csv");
+        let logs = temp_file(
+            "2023-04-12 10:20:30.000, BLE, INFO, [BLE] connected\n",
+            ".csv",
+        );
+
+        assert!(matches!(infer_file_type(&with_header), OutputType::raw));
+        assert!(matches!(infer_file_type(&without_header), OutputType::raw));
+        assert!(matches!(infer_file_type(&comma_lists), OutputType::raw));
+        assert!(matches!(infer_file_type(&points), OutputType::points));
+        assert!(matches!(infer_file_type(&logs), OutputType::logs));
+
+        for path in [with_header, without_header, comma_lists, points, logs] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,10 +1,16 @@
-use polars::prelude::{DataFrame, DataType, Field, Schema};
+use polars::prelude::{DataFrame, DataType, Field, Schema, Series};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+};
 use timespan::DatedData;
 
 use crate::{
     df::{raw::RawDf, score::ScoreDf},
+    error::DataResult,
     series::ToVec,
 };
 
@@ -51,7 +57,22 @@ impl ToJS<Vec<DatedData<RawDfJS>>> for Vec<DatedData<Box<RawDf>>> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Writes the `*DfJS` form of `df` as a single json object for the frontend, an empty
+/// frame is written as `[]`.
+pub fn write_js_json<T, D>(path: &PathBuf, df: &D) -> DataResult<()>
+where
+    T: Serialize,
+    D: ToJS<T> + Deref<Target = DataFrame>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    match df.height() {
+        0 => writer.write_all(b"[]")?,
+        _ => serde_json::to_writer(&mut writer, &df.to_js()).map_err(std::io::Error::from)?,
+    }
+    Ok(writer.flush()?)
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ScoreDfJS {
     pub t: Vec<Option<i64>>,
     pub score: Vec<Option<f64>>,
@@ -60,6 +81,16 @@ pub struct ScoreDfJS {
     pub activity: Vec<String>,
 }
 
+impl ScoreDfJS {
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<ScoreDfJS, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(bytes)
+    }
+}
+
 impl From<DataFrame> for ScoreDfJS {
     fn from(df: DataFrame) -> Self {
         ScoreDfJS {
@@ -81,7 +112,7 @@ impl From<DataFrame> for ScoreDfJS {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct RawDfJS {
     pub t: Vec<Option<i64>>,
     pub left: Vec<Option<Vec<i32>>>,
@@ -90,6 +121,30 @@ pub struct RawDfJS {
     pub gyro: Vec<Option<Vec<i32>>>,
     pub v: Vec<Option<i32>>,
     pub movement: Option<Vec<Option<f64>>>,
+    pub alpha: Option<Vec<Option<Vec<f64>>>>,
+    pub beta: Option<Vec<Option<Vec<f64>>>>,
+    pub bend: Option<Vec<Option<f64>>>,
+}
+
+impl RawDfJS {
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<RawDfJS, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(bytes)
+    }
+}
+
+fn f64_lists(series: &Series) -> Option<Vec<Option<Vec<f64>>>> {
+    match series.list() {
+        Ok(ca) => Some(
+            ca.into_iter()
+                .map(|x| x.map(|x| ToVec::<f64>::to_vec_unchecked(&x)))
+                .collect(),
+        ),
+        Err(_) => None,
+    }
 }
 
 impl From<RawDf> for RawDfJS {
@@ -113,6 +168,18 @@ impl From<RawDf> for RawDfJS {
                 Ok(series) => Some(series.to_vec()),
                 Err(_) => None,
             },
+            alpha: match df.0.column("alpha") {
+                Ok(series) => f64_lists(series),
+                Err(_) => None,
+            },
+            beta: match df.0.column("beta") {
+                Ok(series) => f64_lists(series),
+                Err(_) => None,
+            },
+            bend: match df.0.column("bend") {
+                Ok(series) => Some(series.to_vec()),
+                Err(_) => None,
+            },
         }
     }
 }
@@ -161,7 +228,7 @@ fn gen_sensor_fields(n: usize, prefix: &str) -> Vec<Field> {
         .collect()
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum OutputType {
     points,
@@ -202,3 +269,62 @@ impl OutputType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{write_js_json, RawDfJS, ScoreDfJS};
+    use crate::df::{score::tests::fixture, tests::temp_file};
+
+    #[test]
+    fn msgpack_round_trip() {
+        let score = ScoreDfJS {
+            t: vec![Some(1681294830000), None],
+            score: vec![Some(80.0), Some(81.5)],
+            posture: vec![Some(70.0), None],
+            movement: vec![Some(10.0), Some(12.0)],
+            activity: vec!["office".into(), "".into()],
+        };
+        assert_eq!(
+            ScoreDfJS::from_msgpack(&score.to_msgpack().unwrap()).unwrap(),
+            score
+        );
+
+        let raw = RawDfJS {
+            t: vec![Some(1681294830000)],
+            left: vec![Some(vec![1, 2, 3])],
+            right: vec![Some(vec![4, 5, 6])],
+            acc: vec![Some(vec![0, 0, 1000])],
+            gyro: vec![None],
+            v: vec![Some(4000)],
+            movement: None,
+            alpha: Some(vec![Some(vec![0.1, 0.2])]),
+            beta: None,
+            bend: Some(vec![Some(12.5)]),
+        };
+        assert_eq!(
+            RawDfJS::from_msgpack(&raw.to_msgpack().unwrap()).unwrap(),
+            raw
+        );
+    }
+
+    #[test]
+    fn js_json_round_trip() {
+        let t0 = 1_681_294_830_000;
+        let df = fixture(
+            &[t0, t0 + 1000, t0 + 2000],
+            vec![Some(80.0), None, Some(82.0)],
+        );
+        let path = temp_file("", ".json");
+        write_js_json(&path, &df).unwrap();
+        let js: ScoreDfJS = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(js.score.len(), df.height());
+        assert_eq!(js.t[0], Some(t0));
+
+        write_js_json(&path, &fixture(&[], vec![])).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        fs::remove_file(path).unwrap();
+    }
+}
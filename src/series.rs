@@ -186,12 +186,12 @@ impl ToVec<NaiveDateTime> for Logical<DatetimeType, Int64Type> {
             .collect()
     }
 
+    /// timestamps out of the range of `NaiveDateTime` (e.g. corrupt values) are skipped
     fn to_vec_unchecked(&self) -> Vec<NaiveDateTime> {
         self.0
             .into_iter()
-            .map(|x| {
+            .filter_map(|x| {
                 NaiveDateTime::from_timestamp_millis(x.expect("could not unwrap datetime int"))
-                    .expect("invalid datetime int")
             })
             .collect()
     }
@@ -266,3 +266,27 @@ impl ToSeries for Vec<&Vec<i16>> {
         ListChunked::from_iter(self.into_iter().map(|v| v.to_series())).into_series()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use polars::prelude::*;
+
+    use super::ToVec;
+
+    #[test]
+    fn skip_out_of_range_timestamps() {
+        let t = Series::new("t", [1_681_294_830_000i64, i64::MAX])
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+            .unwrap();
+        let t = t.datetime().unwrap();
+
+        let checked: Vec<Option<NaiveDateTime>> = t.to_vec();
+        let unchecked: Vec<NaiveDateTime> = t.to_vec_unchecked();
+
+        assert_eq!(checked.len(), 2);
+        assert!(checked[0].is_some());
+        assert_eq!(checked[1], None);
+        assert_eq!(unchecked, vec![checked[0].unwrap()]);
+    }
+}
@@ -1,58 +1,69 @@
 pub mod daily_activities;
 pub mod feedback;
+pub mod loaded_user;
 pub mod metadata;
 pub mod stats;
 
 use crate::df::raw::RawDf;
 use crate::df::write_df;
 use crate::logs::Logs;
+use crate::utils::fnv::Fnv1a;
 use crate::{
     df::score::{ScoreDf, ScoreDfSummary},
-    feedback::{BackpainFeedback, RectifyFeedback},
+    feedback::{dedup_feedback, BackpainFeedback, NumericFeedback, RectifyFeedback},
     fs::{list_files, MatchStringPattern},
     logs::LogEntry,
     misc::parse_dart_timestring,
-    user::daily_activities::DailyActivities,
+    user::daily_activities::{DailyActivities, DailyActivity},
 };
 use anyhow::Result;
 use rayon::prelude::*;
 use regex::Regex;
 
 use std::fs::File;
+use std::hash::Hasher;
 use std::io::Write;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 use std::{
     cell::RefCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::{read_to_string, DirEntry},
     path::PathBuf,
     str::FromStr,
 };
 
-use chrono::NaiveDate;
-use polars::prelude::{DataFrame, PolarsResult};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use flex_rs_core::measurement::Measurement;
+use polars::prelude::{DataFrame, DataType, NamedFrom, PolarsResult, Series, TimeUnit};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use timespan::*;
 
 use crate::{
-    df::create_user_df,
+    df::{create_user_df_with, CsvReadOptions},
+    error::DataResult,
     fs::{
         find_inital_app_start, find_sensors, find_uuid_dirs, find_uuids_after, parse_subdirs,
-        GetPaths, ParsedDir,
+        sort_dirs, GetPaths, ParsedDir,
     },
     schema::OutputType,
 };
 
-use self::{feedback::FeedbackType, metadata::UserMetadata};
+use self::{
+    feedback::FeedbackType,
+    loaded_user::{folder_file_name, LoadedUser},
+    metadata::UserMetadata,
+};
 use super::df::time_bound_df::TimeBoundDf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserScoreSummary {
     pub overall_summary: ScoreDfSummary,
     pub daily_summaries: Vec<DatedData<ScoreDfSummary>>,
+    #[serde(default)]
+    pub weekly_summaries: Vec<DatedData<ScoreDfSummary>>,
 }
 
 //pub type Memo<T> = Arc<Mutex<RefCell<Option<T>>>>;
@@ -78,11 +89,41 @@ impl<T> Memo<T> {
     pub fn new(user: Option<T>) -> Memo<T> {
         Memo(Arc::new(Mutex::new(RefCell::new(user))))
     }
+
+    /// the cached value, `f` only runs if nothing is cached yet
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> T
+    where
+        T: Clone,
+    {
+        let guard = self.lock().unwrap();
+        let mut cache = guard.borrow_mut();
+        cache.get_or_insert_with(f).clone()
+    }
+
+    /// like [`Memo::get_or_init`], nothing is cached if `f` fails
+    pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<T, E>
+    where
+        T: Clone,
+    {
+        let guard = self.lock().unwrap();
+        let mut cache = guard.borrow_mut();
+        if let Some(value) = cache.as_ref() {
+            return Ok(value.clone());
+        }
+        let value = f()?;
+        *cache = Some(value.clone());
+        Ok(value)
+    }
+
+    pub fn clear(&self) {
+        *self.lock().unwrap().borrow_mut() = None;
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
+    #[serde(serialize_with = "serialize_sorted_dirs")]
     pub dirs: HashSet<ParsedDir>,
     pub metadata: RefCell<UserMetadata>,
     #[serde(skip)]
@@ -91,6 +132,17 @@ pub struct User {
     last_raw_df_date: Memo<NaiveDate>,
     #[serde(skip)]
     score_df: Memo<ScoreDf>,
+    /// timezone and delimiter of the csv files of this user, see [`User::with_csv_options`]
+    #[serde(skip)]
+    csv_options: CsvReadOptions,
+}
+
+/// writes the dirs in the order of [`sort_dirs`], so the json of a user is reproducible
+fn serialize_sorted_dirs<S: serde::Serializer>(
+    dirs: &HashSet<ParsedDir>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(sort_dirs(dirs.iter().cloned()))
 }
 
 impl Clone for User {
@@ -102,10 +154,83 @@ impl Clone for User {
             raw_df: Memo::default(),
             score_df: Memo::default(),
             last_raw_df_date: Memo::default(),
+            csv_options: self.csv_options.clone(),
         };
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeError;
+
+/// One row per user with the numeric encoding of the latest rectify and backpain feedback,
+/// plus `uuid`, `rectify_t` and `backpain_t`. Missing feedback is null.
+pub fn feedback_dataframe(users: &[User]) -> PolarsResult<DataFrame> {
+    fn numeric_columns<T: NumericFeedback>(
+        prefix: &str,
+        feedback: Vec<Option<TimedData<T>>>,
+    ) -> PolarsResult<Vec<Series>> {
+        let mut columns = vec![Series::new(
+            &format!("{}_t", prefix),
+            feedback
+                .iter()
+                .map(|x| x.as_ref().map(|x| x.time.timestamp_millis()))
+                .collect::<Vec<Option<i64>>>(),
+        )
+        .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?];
+        let values = feedback
+            .iter()
+            .map(|x| x.as_ref().map(|x| x.data.to_numeric_vec()))
+            .collect::<Vec<Option<Vec<Option<f64>>>>>();
+        for (i, name) in T::numeric_columns().into_iter().enumerate() {
+            columns.push(Series::new(
+                name,
+                values
+                    .iter()
+                    .map(|x| x.as_ref().and_then(|x| x[i]))
+                    .collect::<Vec<Option<f64>>>(),
+            ));
+        }
+        Ok(columns)
+    }
+
+    let mut columns = vec![Series::new(
+        "uuid",
+        users
+            .iter()
+            .map(|x| x.id.to_string())
+            .collect::<Vec<String>>(),
+    )];
+    columns.append(&mut numeric_columns(
+        "rectify",
+        users
+            .iter()
+            .map(|x| x.get_rectify_feedback().pop())
+            .collect(),
+    )?);
+    columns.append(&mut numeric_columns(
+        "backpain",
+        users
+            .iter()
+            .map(|x| x.get_backpain_feedback().pop())
+            .collect(),
+    )?);
+    DataFrame::new(columns)
+}
+
+/// files which can't be read are skipped
+fn read_feedback_files(files: Vec<TimedData<PathBuf>>) -> Vec<TimedData<String>> {
+    files
+        .into_iter()
+        .filter_map(|x| match read_to_string(&x.data) {
+            Ok(string) => Some(TimedData {
+                time: x.time,
+                data: string,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
 pub fn gen_users(path: &PathBuf, start_from: Option<NaiveDate>) -> Vec<User> {
     find_uuids_after(
         &parse_subdirs(&path),
@@ -125,6 +250,7 @@ impl User {
             raw_df: Memo::default(),
             score_df: Memo::default(),
             last_raw_df_date: Memo::default(),
+            csv_options: CsvReadOptions::default(),
         }
     }
 
@@ -147,7 +273,59 @@ impl User {
             raw_df: Memo::default(),
             score_df: Memo::default(),
             last_raw_df_date: Memo::default(),
+            csv_options: CsvReadOptions::default(),
+        }
+    }
+
+    /// reads the csv files of the user with `options`, e.g. for testers in another timezone
+    pub fn with_csv_options(mut self, options: CsvReadOptions) -> User {
+        self.csv_options = options;
+        self
+    }
+
+    /// Combines two instances of the same user, e.g. found under different data roots.
+    /// Dirs and sensors are united, feedback and activities are merged without duplicates
+    /// and the earliest app start is kept, the caches start empty.
+    pub fn merge(self, other: User) -> Result<User, MergeError> {
+        if self.id != other.id {
+            return Err(MergeError);
         }
+
+        let mut dirs = self.dirs;
+        dirs.extend(other.dirs);
+
+        let mut m = self.metadata.into_inner();
+        let mut other_m = other.metadata.into_inner();
+        m.sensors.extend(other_m.sensors);
+        m.initial_app_start = match (m.initial_app_start, other_m.initial_app_start) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        m.phone = m.phone.or(other_m.phone);
+        m.app_version = m.app_version.or(other_m.app_version);
+        m.app_feedback.append(&mut other_m.app_feedback);
+        m.app_feedback.sort_by_key(|x| x.time);
+        m.app_feedback = dedup_feedback(m.app_feedback);
+        m.backpain_feedback.append(&mut other_m.backpain_feedback);
+        m.backpain_feedback.sort_by_key(|x| x.time);
+        m.backpain_feedback = dedup_feedback(m.backpain_feedback);
+        m.activities = match (m.activities, other_m.activities) {
+            (Some(a), Some(b)) => Some(a.merge(b)),
+            (a, b) => a.or(b),
+        };
+        // the dirs may overlap, both have to be computed again for the merged dirs
+        m.total_wear_seconds = None;
+        m.fingerprint = None;
+
+        Ok(User {
+            id: self.id,
+            dirs,
+            metadata: RefCell::new(m),
+            raw_df: Memo::default(),
+            score_df: Memo::default(),
+            last_raw_df_date: Memo::default(),
+            csv_options: self.csv_options,
+        })
     }
 
     pub fn gen_summary(&self) -> Option<UserScoreSummary> {
@@ -165,11 +343,22 @@ impl User {
                     None
                 }
             })
+            .filter(|x| x.data.average_score.is_some())
+            .collect::<Vec<DatedData<ScoreDfSummary>>>();
+        let weekly_summaries = df
+            .get_weeks(Some(50))
+            .par_iter()
+            .map(|x| DatedData {
+                time: x.time,
+                data: x.data.summary(),
+            })
+            .filter(|x| x.data.average_score.is_some())
             .collect::<Vec<DatedData<ScoreDfSummary>>>();
 
         Some(UserScoreSummary {
             overall_summary: df.summary(),
             daily_summaries: summaries,
+            weekly_summaries,
         })
     }
 
@@ -178,13 +367,33 @@ impl User {
         let mut m = self.metadata.borrow_mut();
         m.initial_app_start = find_inital_app_start(&self.dirs);
         m.sensors = find_sensors(&self.dirs.clone().to_paths());
-        if let Some(dir) = self.dirs.iter().last() {
-            m.phone = Some(dir.phone.clone());
-            m.app_version = Some(dir.app_version.clone());
+        if let Some(dir) = self.latest_dir() {
+            m.phone = Some(dir.phone);
+            m.app_version = Some(dir.app_version);
         };
         m.activities = Some(DailyActivities::from(self.dirs.clone()));
         m.app_feedback = self.get_rectify_feedback();
         m.backpain_feedback = self.get_backpain_feedback();
+        m.fingerprint = Some(self.fingerprint());
+        m.total_wear_seconds = Some(self.total_wear_seconds());
+    }
+
+    /// combined content fingerprint of all dirs, compare it to `metadata.fingerprint`
+    /// to check if the data changed since the metadata was generated. It hashes the sorted
+    /// dir fingerprints as little endian bytes, so it is stable across builds.
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprints = self
+            .dirs
+            .iter()
+            .map(|x| x.content_fingerprint())
+            .collect::<Vec<u64>>();
+        fingerprints.sort();
+
+        let mut hasher = Fnv1a::default();
+        fingerprints
+            .iter()
+            .for_each(|x| hasher.write(&x.to_le_bytes()));
+        hasher.finish()
     }
 
     pub fn get_df(
@@ -192,32 +401,50 @@ impl User {
         output_type: OutputType,
         date: Option<NaiveDate>,
     ) -> PolarsResult<DataFrame> {
-        create_user_df(&self.dirs.clone().to_paths(), output_type.clone(), date)
+        create_user_df_with(
+            &self.dirs.clone().to_paths(),
+            output_type.clone(),
+            date,
+            &self.csv_options,
+        )
     }
 
     pub fn get_score_df(&self) -> ScoreDf {
-        let guard = self.score_df.lock().unwrap();
-        let mut cache = guard.borrow_mut();
-
-        if cache.is_none() {
-            *cache = Some(match self.get_df(OutputType::points, None) {
+        self.score_df
+            .get_or_init(|| match self.get_df(OutputType::points, None) {
                 Ok(df) => ScoreDf(df),
                 _ => ScoreDf::dummy(),
             })
-        }
-
-        ScoreDf(cache.as_deref().unwrap().clone())
     }
 
-    pub fn get_raw_df(&self, date: Option<NaiveDate>) -> RawDf {
-        let guard = self.raw_df.lock().unwrap();
-        let mut cache = guard.borrow_mut();
+    /// the raw data of `date` (or all days), it is cached until another date is requested
+    pub fn get_raw_df(&self, date: Option<NaiveDate>) -> PolarsResult<RawDf> {
+        let date_guard = self.last_raw_df_date.lock().unwrap();
+        let mut last_date = date_guard.borrow_mut();
 
-        if cache.is_none() || *self.last_raw_df_date.lock().unwrap().borrow() != date {
-            *cache = Some(RawDf(self.get_df(OutputType::raw, date).unwrap()));
+        if *last_date != date {
+            self.raw_df.clear();
+            *last_date = date;
         }
 
-        RawDf(cache.as_deref().unwrap().clone())
+        self.raw_df
+            .get_or_try_init(|| Ok(RawDf(self.get_df(OutputType::raw, date)?)))
+    }
+
+    /// the measurement closest to `time`, at most a second away
+    pub fn measurement_at(&self, time: NaiveDateTime) -> DataResult<Option<Measurement>> {
+        self.measurement_at_with(time, Duration::seconds(1))
+    }
+
+    /// loads (or reuses) the raw data of the day of `time`
+    pub fn measurement_at_with(
+        &self,
+        time: NaiveDateTime,
+        max_distance: Duration,
+    ) -> DataResult<Option<Measurement>> {
+        Ok(self
+            .get_raw_df(Some(time.date()))?
+            .measurement_at(time, max_distance))
     }
 
     pub fn find_in_logs(&self, regex: Regex) -> Vec<LogEntry> {
@@ -263,10 +490,52 @@ impl User {
             .collect()
     }
 
+    /// which feedback was submitted first, compared by the earliest submission of each type;
+    /// `None` unless both rectify and backpain feedback exist
+    pub fn feedback_order(&self) -> Option<(FeedbackType, FeedbackType)> {
+        let first = |t: FeedbackType| self.feedback_files(t).into_iter().map(|x| x.time).min();
+        match (first(FeedbackType::Rectify), first(FeedbackType::Backpain)) {
+            (Some(rectify), Some(backpain)) if backpain < rectify => {
+                Some((FeedbackType::Backpain, FeedbackType::Rectify))
+            }
+            (Some(_), Some(_)) => Some((FeedbackType::Rectify, FeedbackType::Backpain)),
+            _ => None,
+        }
+    }
+
+    /// `dirs` in a deterministic order, the latest app start last
+    pub fn sorted_dirs(&self) -> Vec<ParsedDir> {
+        sort_dirs(self.dirs.clone())
+    }
+
+    /// the dir with the latest app start, it has the current phone and app version
+    pub fn latest_dir(&self) -> Option<ParsedDir> {
+        self.sorted_dirs().pop()
+    }
+
+    /// raw feedback of `feedback_type` submitted within `ts`, oldest first. Only the files
+    /// with a time inside `ts` in their name are read.
+    pub fn get_feedback_in_range(
+        &self,
+        feedback_type: FeedbackType,
+        ts: Timespan,
+    ) -> Vec<TimedData<String>> {
+        read_feedback_files(
+            self.feedback_files(feedback_type)
+                .into_iter()
+                .filter(|x| ts.is_inside(x.time))
+                .collect(),
+        )
+    }
+
     fn get_feedback(&self, feedback_type: FeedbackType) -> Vec<TimedData<String>> {
+        read_feedback_files(self.feedback_files(feedback_type))
+    }
+
+    /// paths of the feedback files with the submission time from their name, oldest first
+    fn feedback_files(&self, feedback_type: FeedbackType) -> Vec<TimedData<PathBuf>> {
         let mut candidates = self
-            .dirs
-            .clone()
+            .sorted_dirs()
             .to_paths()
             .iter()
             .map(|x| {
@@ -282,23 +551,23 @@ impl User {
 
         candidates
             .into_iter()
-            .filter_map(|e| match read_to_string(e.path()) {
-                Ok(string) => {
-                    let time = parse_dart_timestring(
-                        e.path()
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                            .split_once("_")
-                            .unwrap()
-                            .1,
-                    )
-                    .unwrap();
-                    Some(TimedData { time, data: string })
+            .map(|e| {
+                let time = parse_dart_timestring(
+                    e.path()
+                        .file_name()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                        .split_once("_")
+                        .unwrap()
+                        .1,
+                )
+                .unwrap();
+                TimedData {
+                    time,
+                    data: e.path(),
                 }
-                _ => None,
             })
             .collect()
     }
@@ -317,20 +586,16 @@ impl User {
             m.sensors = find_sensors(&self.dirs.clone().to_paths())
         }
 
+        let latest = self.latest_dir();
+
         m.phone = match m.phone.clone() {
             Some(phone) => Some(phone),
-            _ => match self.dirs.iter().next() {
-                Some(dir) => Some(dir.phone.clone()),
-                _ => None,
-            },
+            _ => latest.as_ref().map(|dir| dir.phone.clone()),
         };
 
         m.app_version = match m.app_version.clone() {
             Some(app_version) => Some(app_version),
-            _ => match self.dirs.iter().next() {
-                Some(dir) => Some(dir.app_version.clone()),
-                _ => None,
-            },
+            _ => latest.map(|dir| dir.app_version),
         };
 
         m.activities = Some(self.get_daily_activities());
@@ -338,47 +603,66 @@ impl User {
         Ok(())
     }
 
+    /// activity blocks separated by breaks of more than 5 minutes
     pub fn get_activity_blocks(&self) -> Vec<Timespan> {
+        self.get_activity_blocks_with(300000)
+    }
+
+    /// activity blocks separated by breaks of more than `threshold_ms`
+    pub fn get_activity_blocks_with(&self, threshold_ms: i64) -> Vec<Timespan> {
         match self.get_df(OutputType::points, None) {
-            Ok(df) => ScoreDf(df).get_activity_timespans(300000),
+            Ok(df) => ScoreDf(df).get_activity_timespans(threshold_ms),
             Err(_) => vec![],
         }
     }
 
+    /// summed length of the activity blocks, see [`User::get_activity_blocks`]
+    pub fn total_wear_seconds(&self) -> u64 {
+        self.get_activity_blocks()
+            .iter()
+            .map(|x| (x.end - x.begin).num_seconds().max(0) as u64)
+            .sum()
+    }
+
+    /// activity blocks with the self reported activity closest in time (at most 12 hours away)
+    pub fn annotated_sessions(&self) -> Vec<DatedData<(Timespan, DailyActivity)>> {
+        self.get_daily_activities()
+            .annotate(self.get_activity_blocks(), Duration::hours(12))
+    }
+
+    /// score summary per self reported activity, see [`DailyActivities::summarize_scores`]
+    pub fn score_by_activity(&self) -> HashMap<DailyActivity, ScoreDfSummary> {
+        self.get_daily_activities()
+            .summarize_scores(&self.get_score_df())
+    }
+
     pub fn get_logs(&self) -> PolarsResult<DataFrame> {
         self.get_df(OutputType::logs, None)
     }
 
-    fn write_df(&self, base_path: PathBuf, output_type: OutputType) {
+    fn write_df(&self, base_path: PathBuf, output_type: OutputType) -> Result<()> {
         let mut path = base_path.clone();
+        path.push(folder_file_name(&output_type));
 
-        path.push(match output_type {
-            OutputType::points => "score.parquet",
-            OutputType::raw => "raw.parquet",
-            OutputType::logs => "logs.parquet",
-        });
+        Ok(write_df(&path, &mut self.get_df(output_type, None)?)?)
+    }
 
-        write_df(
-            &path,
-            &mut self
-                .get_df(output_type.clone(), None)
-                .expect(format!("could not create df of type {:?}", output_type).as_str()),
-        );
+    /// reads a folder written by [`User::create_user_folder`]
+    pub fn from_folder(path: PathBuf) -> Result<LoadedUser> {
+        LoadedUser::from_folder(path)
     }
 
-    pub fn create_user_folder(&self, base_path: PathBuf) {
+    /// stops at the first frame which can't be created or written
+    pub fn create_user_folder(&self, base_path: PathBuf) -> Result<()> {
         let mut path = base_path.clone();
         path.push("metadata.json");
-        let serde_val = serde_json::to_string_pretty(&self.metadata.borrow().clone()).unwrap();
+        let serde_val = serde_json::to_string_pretty(&self.metadata.borrow().clone())?;
         let output_buf: &[u8] = serde_val.as_bytes();
-        File::create(path)
-            .unwrap()
-            .write_all(&output_buf)
-            .expect("could not write metadata");
+        File::create(path)?.write_all(&output_buf)?;
 
-        self.write_df(base_path.clone(), OutputType::logs);
-        self.write_df(base_path.clone(), OutputType::raw);
-        self.write_df(base_path, OutputType::points);
+        self.write_df(base_path.clone(), OutputType::logs)?;
+        self.write_df(base_path.clone(), OutputType::raw)?;
+        self.write_df(base_path, OutputType::points)
     }
 }
 
@@ -386,18 +670,20 @@ impl Into<ScoreDfSummary> for Vec<ScoreDfSummary> {
     fn into(self) -> ScoreDfSummary {
         let iter = self.iter();
         ScoreDfSummary {
-            average_score: iter.clone().map(|x| x.average_score).sum::<f64>() / (self.len() as f64),
+            average_score: {
+                let averages = iter
+                    .clone()
+                    .filter_map(|x| x.average_score)
+                    .collect::<Vec<f64>>();
+                if averages.is_empty() {
+                    None
+                } else {
+                    Some(averages.iter().sum::<f64>() / averages.len() as f64)
+                }
+            },
             duration: iter.clone().map(|x| x.duration).sum(),
-            min: iter
-                .clone()
-                .map(|x| x.min)
-                .reduce(|a, b| if a > b { b } else { a })
-                .unwrap(),
-            max: iter
-                .clone()
-                .map(|x| x.max)
-                .reduce(|a, b| if a < b { b } else { a })
-                .unwrap(),
+            min: iter.clone().filter_map(|x| x.min).reduce(f64::min),
+            max: iter.clone().filter_map(|x| x.max).reduce(f64::max),
         }
     }
 }
@@ -413,3 +699,401 @@ impl Into<Memo<User>> for Uuid {
         Memo::new(Some(self.into()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use chrono::NaiveDate;
+    use polars::prelude::DataType;
+    use timespan::Timespan;
+    use uuid::Uuid;
+
+    use super::{
+        daily_activities::DailyActivity, feedback_dataframe, FeedbackType, Memo, MergeError, User,
+    };
+    use crate::{
+        df::CsvReadOptions,
+        feedback::{tests::backpain_json, BackpainFeedback},
+        fs::ParsedDir,
+        schema::OutputType,
+        TimedData,
+    };
+
+    const UUID: &str = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+
+    fn dir(date: &str) -> ParsedDir {
+        ParsedDir::from_str(&format!(
+            "{}_10_20_30_Apple_iPhone12_1.2.3-45_{}",
+            date, UUID
+        ))
+        .unwrap()
+    }
+
+    fn user(dirs: Vec<ParsedDir>) -> User {
+        let mut user = User::new(Uuid::parse_str(UUID).unwrap());
+        user.dirs = dirs.into_iter().collect();
+        user
+    }
+
+    /// a dir in a new temp folder with one csv file of `lines` in `subdir`
+    fn data_dir(subdir: &str, lines: Vec<String>) -> ParsedDir {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        fs::create_dir_all(root.join(subdir)).unwrap();
+        fs::write(
+            root.join(subdir).join("1681294830000-1681294831000.csv"),
+            lines.join("\n") + "\n",
+        )
+        .unwrap();
+
+        let mut parsed = dir("2023-04-12");
+        parsed.path = root;
+        parsed
+    }
+
+    #[test]
+    fn memo_initializes_once() {
+        let memo: Memo<usize> = Memo::default();
+        let mut calls = 0;
+        let mut counted = |v: usize| {
+            calls += 1;
+            v
+        };
+
+        assert_eq!(memo.get_or_init(|| counted(42)), 42);
+        assert_eq!(memo.get_or_init(|| counted(0)), 42);
+        assert_eq!(calls, 1);
+
+        memo.clear();
+        assert_eq!(memo.get_or_init(|| 7), 7);
+    }
+
+    #[test]
+    fn memo_keeps_nothing_on_error() {
+        let memo: Memo<usize> = Memo::default();
+
+        assert_eq!(memo.get_or_try_init(|| Err("failed")), Err("failed"));
+        assert_eq!(memo.get_or_try_init(|| Ok::<_, &str>(42)), Ok(42));
+        assert_eq!(memo.get_or_try_init(|| Err("failed")), Ok(42));
+    }
+
+    #[test]
+    fn metadata_from_latest_dir() {
+        let dir_with = |date: &str, phone: &str, version: &str| {
+            ParsedDir::from_str(&format!("{}_10_20_30_{}_{}_{}", date, phone, version, UUID))
+                .unwrap()
+        };
+        let old = dir_with("2023-04-12", "Apple_iPhone12", "1.2.3-45");
+        let new = dir_with("2023-05-02", "Google_Pixel7", "1.3.0-50");
+
+        for dirs in [
+            vec![old.clone(), new.clone()],
+            vec![new.clone(), old.clone()],
+        ] {
+            let u = user(dirs);
+            u.set_metadata().unwrap();
+
+            let m = u.metadata.borrow();
+            assert_eq!(m.app_version, Some(new.app_version.clone()));
+            assert_eq!(m.phone, Some(new.phone.clone()));
+        }
+    }
+
+    #[test]
+    fn dirs_serialized_in_order() {
+        let dates = ["2023-04-14", "2023-04-12", "2023-04-13", "2023-03-01"];
+        let a = user(dates.iter().map(|x| dir(x)).collect());
+        let b = user(dates.iter().rev().map(|x| dir(x)).collect());
+
+        let json = serde_json::to_string(&a).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&b).unwrap());
+        assert!(json.find("2023-03-01").unwrap() < json.find("2023-04-14").unwrap());
+    }
+
+    #[test]
+    fn sensors_serialized_in_order() {
+        let sensors = ["FTB33", "FT1A2", "FT0FF", "FT9C4"];
+        let a = user(vec![]);
+        let b = user(vec![]);
+        a.metadata.borrow_mut().sensors = sensors.iter().map(|x| x.to_string()).collect();
+        b.metadata.borrow_mut().sensors = sensors.iter().rev().map(|x| x.to_string()).collect();
+
+        let json = serde_json::to_string(&a).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&b).unwrap());
+        assert!(json.contains(r#""sensors":["FT0FF","FT1A2","FT9C4","FTB33"]"#));
+    }
+
+    #[test]
+    fn merge_users() {
+        let a = user(vec![dir("2023-04-12"), dir("2023-04-13")]);
+        let b = user(vec![dir("2023-04-13"), dir("2023-04-14")]);
+        a.metadata.borrow_mut().sensors.insert("FT1A2".into());
+        b.metadata.borrow_mut().sensors.insert("FT3B4".into());
+
+        let feedback = |level: &str| TimedData {
+            time: NaiveDate::from_ymd_opt(2023, 4, 13)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+            data: BackpainFeedback::from_str(&backpain_json(level)).unwrap(),
+        };
+        let activity = |h: u32, data: DailyActivity| TimedData {
+            time: NaiveDate::from_ymd_opt(2023, 4, 13)
+                .unwrap()
+                .and_hms_opt(h, 0, 0)
+                .unwrap(),
+            data,
+        };
+        {
+            let mut m = a.metadata.borrow_mut();
+            m.backpain_feedback = vec![feedback("3")];
+            m.activities = Some(vec![activity(8, DailyActivity::Office)].into());
+            m.fingerprint = Some(1);
+        }
+        {
+            let mut m = b.metadata.borrow_mut();
+            m.backpain_feedback = vec![feedback("3"), feedback("5")];
+            m.activities = Some(
+                vec![
+                    activity(8, DailyActivity::Office),
+                    activity(12, DailyActivity::Travel),
+                ]
+                .into(),
+            );
+        }
+
+        let merged = a.merge(b).unwrap();
+
+        assert_eq!(merged.dirs.len(), 3);
+        let m = merged.metadata.borrow();
+        assert_eq!(m.sensors.len(), 2);
+        assert_eq!(m.backpain_feedback.len(), 2);
+        assert_eq!(m.activities.as_ref().map(|x| x.len()), Some(2));
+        assert_eq!(m.fingerprint, None);
+        assert_eq!(
+            user(vec![]).merge(User::new(Uuid::new_v4())).err(),
+            Some(MergeError)
+        );
+    }
+
+    #[test]
+    fn raw_df_cache_follows_date() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut raw = root.clone();
+        raw.push("raw");
+        fs::create_dir_all(&raw).unwrap();
+
+        let day1 = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        let sensors = vec!["1000"; 18].join(",");
+        for (day, rows) in [(day1, 1), (day2, 2)] {
+            let begin = day.and_hms_opt(0, 0, 1).unwrap().timestamp_millis();
+            let lines: Vec<String> = (0..rows)
+                .map(|i| format!("{},0,0,1000,0,0,0,4000,{}", sensors, begin + i))
+                .collect();
+            let mut file = raw.clone();
+            file.push(format!("{}-{}.csv", begin, begin + 1000));
+            fs::write(file, lines.join("\n") + "\n").unwrap();
+        }
+
+        let mut d = dir("2023-04-12");
+        d.path = root.clone();
+        let u = user(vec![d]);
+
+        assert_eq!(u.get_raw_df(Some(day1)).unwrap().height(), 1);
+        assert_eq!(u.get_raw_df(Some(day2)).unwrap().height(), 2);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn measurement_without_raw_files_is_error() {
+        let time = NaiveDate::from_ymd_opt(2023, 4, 12)
+            .unwrap()
+            .and_hms_opt(10, 20, 30)
+            .unwrap();
+
+        assert!(user(vec![]).measurement_at(time).is_err());
+    }
+
+    #[test]
+    fn feedback_order_by_submission_time() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut feedback = root.clone();
+        feedback.push("feedback");
+        fs::create_dir_all(&feedback).unwrap();
+
+        let mut d = dir("2023-04-12");
+        d.path = root.clone();
+        let u = user(vec![d]);
+
+        let mut backpain = feedback.clone();
+        backpain.push("backpain_2023-04-12 10_20_30.json");
+        fs::write(backpain, "{}").unwrap();
+        assert_eq!(u.feedback_order(), None);
+
+        let mut rectify = feedback.clone();
+        rectify.push("rectify_2023-04-13 08_00_00.json");
+        fs::write(rectify, "{}").unwrap();
+        assert_eq!(
+            u.feedback_order(),
+            Some((FeedbackType::Backpain, FeedbackType::Rectify))
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn wear_time_in_metadata() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let points = root.join("points");
+        fs::create_dir_all(&points).unwrap();
+        // 10 minutes, a break of 50 minutes and another 5 minutes
+        let t0 = 1_681_294_830_000i64;
+        let lines = (0..=10)
+            .chain(60..=65)
+            .map(|i| format!("{},80.0,70.0,10.0,office", t0 + i * 60_000))
+            .collect::<Vec<String>>();
+        fs::write(
+            points.join(format!("{}-{}.csv", t0, t0 + 65 * 60_000)),
+            lines.join("\n") + "\n",
+        )
+        .unwrap();
+
+        let mut d = dir("2023-04-12");
+        d.path = root.clone();
+        let mut u = user(vec![]);
+        u.fill_user(&vec![d]);
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(u.metadata.borrow().total_wear_seconds, Some(15 * 60));
+    }
+
+    #[test]
+    fn feedback_in_range() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let mut feedback = root.clone();
+        feedback.push("feedback");
+        fs::create_dir_all(&feedback).unwrap();
+        for name in [
+            "rectify_2023-04-10 09_00_00.json",
+            "rectify_2023-04-12 10_20_30.json",
+            "rectify_2023-04-20 18_00_00.json",
+            "backpain_2023-04-12 11_00_00.json",
+        ] {
+            fs::write(feedback.join(name), "{}").unwrap();
+        }
+
+        let mut d = dir("2023-04-12");
+        d.path = root.clone();
+        let u = user(vec![d]);
+        let day = NaiveDate::from_ymd_opt(2023, 4, 11).unwrap();
+        let ts = Timespan {
+            begin: day.and_hms_opt(0, 0, 0).unwrap(),
+            end: day.and_hms_opt(0, 0, 0).unwrap() + chrono::Duration::days(7),
+        };
+
+        let in_range = u.get_feedback_in_range(FeedbackType::Rectify, ts);
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(
+            in_range
+                .iter()
+                .map(|x| x.time.to_string())
+                .collect::<Vec<String>>(),
+            vec!["2023-04-12 10:20:30"]
+        );
+    }
+
+    #[test]
+    fn feedback_dataframe_one_row_per_user() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+
+        let users = ["3", "5"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let mut path = root.clone();
+                path.push(i.to_string());
+                path.push("feedback");
+                fs::create_dir_all(&path).unwrap();
+                path.push("backpain_2023-04-12 10_20_30.json");
+                fs::write(&path, backpain_json(level)).unwrap();
+
+                let mut d = dir("2023-04-12");
+                d.path = path.parent().unwrap().parent().unwrap().to_path_buf();
+                let mut u = user(vec![d]);
+                u.id = Uuid::new_v4();
+                u
+            })
+            .collect::<Vec<User>>();
+
+        let df = feedback_dataframe(&users).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert_eq!(
+            df["backpainLevel"]
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<f64>>(),
+            vec![3.0, 5.0]
+        );
+        assert_eq!(df["rectify_t"].null_count(), 2);
+        assert_eq!(df["backpain_t"].null_count(), 0);
+    }
+
+    #[test]
+    fn get_df_in_user_timezone() {
+        let t0 = 1_681_294_830_000i64;
+        let dir = data_dir(
+            "points",
+            (0..2)
+                .map(|i| format!("{},80.0,70.0,10.0,office", t0 + i * 1000))
+                .collect(),
+        );
+        let root = dir.path.clone();
+        let user = user(vec![dir]).with_csv_options(CsvReadOptions {
+            timezone: "America/New_York".into(),
+            ..Default::default()
+        });
+
+        let df = user.get_df(OutputType::points, None).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert!(matches!(
+            df["t"].dtype(),
+            DataType::Datetime(_, Some(tz)) if tz == "America/New_York"
+        ));
+    }
+
+    #[test]
+    fn get_df_with_user_delimiter() {
+        let t0 = 1_681_294_830_000i64;
+        let sensors = vec!["1000"; 18].join(";");
+        let dir = data_dir(
+            "raw",
+            (0..3)
+                .map(|i| format!("{};0;0;1000;0;0;0;4000;{}", sensors, t0 + i * 20))
+                .collect(),
+        );
+        let root = dir.path.clone();
+        let user = user(vec![dir]).with_csv_options(CsvReadOptions {
+            delimiter: b';',
+            ..Default::default()
+        });
+
+        let df = user.get_df(OutputType::raw, None).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(df.height(), 3);
+    }
+}
@@ -1,17 +1,145 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use crate::{
+    df::score::{ScoreDf, ScoreDfSummary},
     fs::{list_files, ParsedDir},
     misc::read_first_line,
 };
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime};
+use polars::prelude::BooleanChunked;
 use serde::{Deserialize, Serialize};
+use timespan::{DatedData, Timespan};
 
 use super::TimedData;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DailyActivities(Vec<TimedData<DailyActivity>>);
 
+impl DailyActivities {
+    pub fn iter(&self) -> std::slice::Iter<'_, TimedData<DailyActivity>> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// the most recent report
+    pub fn latest(&self) -> Option<&TimedData<DailyActivity>> {
+        self.0.iter().max_by_key(|x| x.time)
+    }
+
+    /// the report closest to `time`, if it is at most `max_distance` away
+    pub fn nearest(
+        &self,
+        time: NaiveDateTime,
+        max_distance: Duration,
+    ) -> Option<&TimedData<DailyActivity>> {
+        let distance = |x: &TimedData<DailyActivity>| (x.time - time).num_milliseconds().abs();
+        self.0
+            .iter()
+            .min_by_key(|x| distance(x))
+            .filter(|x| distance(x) <= max_distance.num_milliseconds())
+    }
+
+    /// reports of both, sorted by time, identical reports are only kept once
+    pub fn merge(self, other: DailyActivities) -> DailyActivities {
+        let mut activities = self.0;
+        activities.extend(other.0);
+        activities.sort_by_key(|x| x.time);
+        activities.dedup_by(|a, b| a.time == b.time && a.data == b.data);
+        DailyActivities(activities)
+    }
+
+    /// the latest report at or before `time` on the same day
+    pub fn active_at(&self, time: NaiveDateTime) -> Option<&TimedData<DailyActivity>> {
+        self.0
+            .iter()
+            .filter(|x| x.time <= time && x.time.date() == time.date())
+            .max_by_key(|x| x.time)
+    }
+
+    /// score summary per activity active at the time of the samples (see
+    /// [`DailyActivities::active_at`]), samples without a report count as `DailyActivity::NA`
+    pub fn summarize_scores(&self, df: &ScoreDf) -> HashMap<DailyActivity, ScoreDfSummary> {
+        let labels = df
+            .time()
+            .into_iter()
+            .map(|t| {
+                t.and_then(NaiveDateTime::from_timestamp_millis)
+                    .and_then(|t| self.active_at(t))
+                    .map_or(DailyActivity::NA, |x| x.data.clone())
+            })
+            .collect::<Vec<DailyActivity>>();
+
+        labels
+            .iter()
+            .cloned()
+            .collect::<HashSet<DailyActivity>>()
+            .into_iter()
+            .filter_map(|activity| {
+                let mask: BooleanChunked = labels.iter().map(|x| *x == activity).collect();
+                let part = ScoreDf(df.filter(&mask).ok()?);
+                Some((activity, part.summary()))
+            })
+            .collect()
+    }
+
+    /// pairs each session with the report nearest to its middle, sessions without a
+    /// report within `max_distance` get `DailyActivity::NA`
+    pub fn annotate(
+        &self,
+        sessions: Vec<Timespan>,
+        max_distance: Duration,
+    ) -> Vec<DatedData<(Timespan, DailyActivity)>> {
+        sessions
+            .into_iter()
+            .map(|span| {
+                let middle = span.begin + (span.end - span.begin) / 2;
+                let activity = match self.nearest(middle, max_distance) {
+                    Some(x) => x.data.clone(),
+                    None => DailyActivity::NA,
+                };
+                DatedData {
+                    time: span.begin.date(),
+                    data: (span, activity),
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<TimedData<DailyActivity>>> for DailyActivities {
+    fn from(activities: Vec<TimedData<DailyActivity>>) -> Self {
+        DailyActivities(activities)
+    }
+}
+
+impl IntoIterator for DailyActivities {
+    type Item = TimedData<DailyActivity>;
+    type IntoIter = std::vec::IntoIter<TimedData<DailyActivity>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DailyActivities {
+    type Item = &'a TimedData<DailyActivity>;
+    type IntoIter = std::slice::Iter<'a, TimedData<DailyActivity>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
 impl From<HashSet<ParsedDir>> for DailyActivities {
     fn from(dirs: HashSet<ParsedDir>) -> Self {
         DailyActivities(
@@ -35,10 +163,9 @@ impl From<HashSet<ParsedDir>> for DailyActivities {
                             }
                         )
                         .unwrap(),
-                        data: DailyActivity::from_str(
-                            read_first_line(&file.path()).unwrap().trim(),
-                        )
-                        .unwrap_or(DailyActivity::Other),
+                        data: read_first_line(&file.path())
+                            .and_then(|x| DailyActivity::from_str(x.trim()).ok())
+                            .unwrap_or(DailyActivity::Other),
                     })
                 })
                 .flatten()
@@ -47,7 +174,7 @@ impl From<HashSet<ParsedDir>> for DailyActivities {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DailyActivity {
     Office,
     Homeoffice,
@@ -76,3 +203,96 @@ impl FromStr for DailyActivity {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+    use timespan::{TimedData, Timespan};
+
+    use super::{DailyActivities, DailyActivity};
+    use crate::df::score::tests::fixture;
+
+    #[test]
+    fn scores_per_active_activity() {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let at = |h: u32| day.and_hms_opt(h, 0, 0).unwrap();
+        let activities = DailyActivities::from(vec![
+            TimedData {
+                time: at(8),
+                data: DailyActivity::Office,
+            },
+            TimedData {
+                time: at(12),
+                data: DailyActivity::Travel,
+            },
+        ]);
+        let df = fixture(
+            &[at(7), at(9), at(10), at(13)].map(|x| x.timestamp_millis()),
+            vec![Some(50.0), Some(80.0), Some(60.0), Some(90.0)],
+        );
+
+        let summaries = activities.summarize_scores(&df);
+
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[&DailyActivity::Office].average_score, Some(70.0));
+        assert_eq!(summaries[&DailyActivity::Travel].average_score, Some(90.0));
+        assert_eq!(summaries[&DailyActivity::NA].average_score, Some(50.0));
+    }
+
+    #[test]
+    fn iterate_reports() {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let activities = DailyActivities::from(vec![
+            TimedData {
+                time: day.and_hms_opt(12, 0, 0).unwrap(),
+                data: DailyActivity::Travel,
+            },
+            TimedData {
+                time: day.and_hms_opt(8, 0, 0).unwrap(),
+                data: DailyActivity::Office,
+            },
+        ]);
+
+        assert_eq!(activities.len(), 2);
+        assert!(!activities.is_empty());
+        assert_eq!(
+            activities
+                .iter()
+                .map(|x| x.data.clone())
+                .collect::<Vec<DailyActivity>>(),
+            vec![DailyActivity::Travel, DailyActivity::Office]
+        );
+        assert_eq!((&activities).into_iter().count(), 2);
+        assert_eq!(activities.latest().unwrap().data, DailyActivity::Travel);
+        assert_eq!(
+            activities.into_iter().last().unwrap().data,
+            DailyActivity::Office
+        );
+        assert!(DailyActivities::from(vec![]).latest().is_none());
+    }
+
+    #[test]
+    fn annotate_sessions_with_nearest_report() {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let activities = DailyActivities::from(vec![TimedData {
+            time: day.and_hms_opt(8, 0, 0).unwrap(),
+            data: DailyActivity::Office,
+        }]);
+        let sessions = vec![
+            Timespan {
+                begin: day.and_hms_opt(9, 0, 0).unwrap(),
+                end: day.and_hms_opt(11, 0, 0).unwrap(),
+            },
+            Timespan {
+                begin: day.succ_opt().unwrap().and_hms_opt(20, 0, 0).unwrap(),
+                end: day.succ_opt().unwrap().and_hms_opt(21, 0, 0).unwrap(),
+            },
+        ];
+
+        let annotated = activities.annotate(sessions, Duration::hours(12));
+
+        assert_eq!(annotated[0].time, day);
+        assert_eq!(annotated[0].data.1, DailyActivity::Office);
+        assert_eq!(annotated[1].data.1, DailyActivity::NA);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use anyhow::Result;
+use polars::prelude::*;
+
+use super::{metadata::UserMetadata, Memo};
+use crate::{
+    df::{raw::RawDf, read_input_file_into_df, score::ScoreDf},
+    schema::OutputType,
+};
+
+/// file name of a frame inside a folder written by [`super::User::create_user_folder`]
+pub(crate) fn folder_file_name(output_type: &OutputType) -> &'static str {
+    match output_type {
+        OutputType::points => "score.parquet",
+        OutputType::raw => "raw.parquet",
+        OutputType::logs => "logs.parquet",
+    }
+}
+
+/// A user read back from a folder written by [`super::User::create_user_folder`].
+/// The frames are only read when they are requested the first time.
+#[derive(Debug)]
+pub struct LoadedUser {
+    pub path: PathBuf,
+    pub metadata: UserMetadata,
+    raw_df: Memo<RawDf>,
+    score_df: Memo<ScoreDf>,
+    logs_df: Memo<DataFrame>,
+}
+
+impl LoadedUser {
+    pub fn from_folder(path: PathBuf) -> Result<LoadedUser> {
+        let mut metadata = path.clone();
+        metadata.push("metadata.json");
+        Ok(LoadedUser {
+            metadata: serde_json::from_str(&read_to_string(metadata)?)?,
+            path,
+            raw_df: Memo::default(),
+            score_df: Memo::default(),
+            logs_df: Memo::default(),
+        })
+    }
+
+    fn read(&self, output_type: OutputType) -> PolarsResult<DataFrame> {
+        let mut path = self.path.clone();
+        path.push(folder_file_name(&output_type));
+        if !path.exists() {
+            return Err(PolarsError::NoData(
+                format!("{:?} does not exist", path).into(),
+            ));
+        }
+        Ok(read_input_file_into_df(path)?)
+    }
+
+    pub fn get_score_df(&self) -> PolarsResult<ScoreDf> {
+        self.score_df
+            .get_or_try_init(|| Ok(ScoreDf(self.read(OutputType::points)?)))
+    }
+
+    pub fn get_raw_df(&self) -> PolarsResult<RawDf> {
+        self.raw_df
+            .get_or_try_init(|| Ok(RawDf(self.read(OutputType::raw)?)))
+    }
+
+    pub fn get_logs(&self) -> PolarsResult<DataFrame> {
+        self.logs_df.get_or_try_init(|| self.read(OutputType::logs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use uuid::Uuid;
+
+    use super::folder_file_name;
+    use crate::{fs::ParsedDir, schema::OutputType, user::User};
+
+    const UUID: &str = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+
+    #[test]
+    fn write_and_load_user_folder() {
+        let mut root = std::env::temp_dir();
+        root.push(Uuid::new_v4().to_string());
+        let data = root.join("data");
+        let folder = root.join("folder");
+        for subdir in ["points", "raw", "logs"] {
+            fs::create_dir_all(data.join(subdir)).unwrap();
+        }
+        fs::create_dir_all(&folder).unwrap();
+
+        let t0 = 1_681_294_830_000i64;
+        let sensors = vec!["1000"; 18].join(",");
+        let write = |subdir: &str, lines: Vec<String>| {
+            fs::write(
+                data.join(subdir).join(format!("{}-{}.csv", t0, t0 + 1000)),
+                lines.join("\n") + "\n",
+            )
+            .unwrap()
+        };
+        write(
+            "points",
+            (0..2)
+                .map(|i| format!("{},80.0,70.0,10.0,office", t0 + i * 1000))
+                .collect(),
+        );
+        write(
+            "raw",
+            (0..3)
+                .map(|i| format!("{},0,0,1000,0,0,0,4000,{}", sensors, t0 + i * 20))
+                .collect(),
+        );
+        write(
+            "logs",
+            vec!["2023-04-12 10:20:30.000,BLE,INFO,connected".to_string()],
+        );
+
+        let mut dir = ParsedDir::from_str(&format!(
+            "2023-04-12_10_20_30_Apple_iPhone12_1.2.3-45_{}",
+            UUID
+        ))
+        .unwrap();
+        dir.path = data;
+        let mut user = User::new(Uuid::parse_str(UUID).unwrap());
+        user.dirs.insert(dir);
+        user.metadata.borrow_mut().sensors.insert("FT1A2".into());
+
+        user.create_user_folder(folder.clone()).unwrap();
+        let loaded = User::from_folder(folder.clone()).unwrap();
+
+        assert!(loaded.metadata.sensors.contains("FT1A2"));
+        assert_eq!(loaded.get_score_df().unwrap().height(), 2);
+        assert_eq!(loaded.get_raw_df().unwrap().height(), 3);
+        assert_eq!(loaded.get_logs().unwrap().height(), 1);
+
+        fs::remove_file(folder.join(folder_file_name(&OutputType::raw))).unwrap();
+        assert!(User::from_folder(folder).unwrap().get_raw_df().is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -10,6 +10,7 @@ use std::collections::HashSet;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserMetadata {
+    #[serde(serialize_with = "serialize_sorted_sensors")]
     pub sensors: HashSet<String>,
     pub initial_app_start: Option<NaiveDateTime>,
     pub number_of_measured_days: Option<usize>,
@@ -19,6 +20,11 @@ pub struct UserMetadata {
     pub activities: Option<DailyActivities>,
     pub app_feedback: Vec<TimedData<RectifyFeedback>>,
     pub backpain_feedback: Vec<TimedData<BackpainFeedback>>,
+    /// content fingerprint of the user's dirs at the time the metadata was created
+    pub fingerprint: Option<u64>,
+    /// summed length of the activity blocks in seconds
+    #[serde(default)]
+    pub total_wear_seconds: Option<u64>,
 }
 
 impl UserMetadata {
@@ -33,6 +39,8 @@ impl UserMetadata {
             activities: None,
             app_feedback: vec![],
             backpain_feedback: vec![],
+            fingerprint: None,
+            total_wear_seconds: None,
         }
     }
 
@@ -56,3 +64,13 @@ impl UserMetadata {
         self
     }
 }
+
+/// writes the sensors sorted, so the json of the metadata is reproducible
+fn serialize_sorted_sensors<S: serde::Serializer>(
+    sensors: &HashSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut sensors = sensors.iter().collect::<Vec<&String>>();
+    sensors.sort();
+    serializer.collect_seq(sensors)
+}
@@ -1 +1,3 @@
+pub mod dated_data;
+pub mod fnv;
 pub mod stats_utils;
@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::Value;
+use timespan::DatedData;
+
+/// Helpers for [`DatedData`] which keep the date and work on the data.
+///
+/// ```
+/// use flex_rs_data::{utils::dated_data::DatedDataExt, DatedData};
+///
+/// let d = DatedData {
+///     time: chrono::NaiveDate::from_ymd_opt(2023, 4, 12).unwrap(),
+///     data: 21,
+/// };
+/// assert_eq!(d.as_ref().map(|x| x * 2).data, 42);
+/// ```
+pub trait DatedDataExt<T> {
+    fn map<U, F: FnOnce(T) -> U>(self, f: F) -> DatedData<U>;
+    fn as_ref(&self) -> DatedData<&T>;
+}
+
+impl<T> DatedDataExt<T> for DatedData<T> {
+    fn map<U, F: FnOnce(T) -> U>(self, f: F) -> DatedData<U> {
+        DatedData {
+            time: self.time,
+            data: f(self.data),
+        }
+    }
+
+    fn as_ref(&self) -> DatedData<&T> {
+        DatedData {
+            time: self.time,
+            data: &self.data,
+        }
+    }
+}
+
+/// One csv line per row with a leading `date` column followed by the fields of the
+/// serialized data (nested objects as `outer.inner`, sorted by name). Data which doesn't
+/// serialize to an object is written to a `data` column, `None` to an empty field.
+pub fn dated_data_to_csv<T: Serialize>(rows: &[DatedData<T>]) -> serde_json::Result<String> {
+    let flat = rows
+        .iter()
+        .map(|x| {
+            let mut fields = BTreeMap::new();
+            flatten_value("", serde_json::to_value(&x.data)?, &mut fields);
+            Ok((x.time, fields))
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    let columns = flat
+        .iter()
+        .flat_map(|x| x.1.keys().cloned())
+        .collect::<BTreeSet<String>>();
+
+    let mut lines = vec![std::iter::once("date".to_string())
+        .chain(columns.iter().cloned())
+        .collect::<Vec<String>>()
+        .join(",")];
+    for (date, fields) in flat {
+        lines.push(
+            std::iter::once(date.to_string())
+                .chain(
+                    columns
+                        .iter()
+                        .map(|c| fields.get(c).cloned().unwrap_or_default()),
+                )
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+    }
+    Ok(lines.join("\n"))
+}
+
+fn flatten_value(prefix: &str, value: Value, fields: &mut BTreeMap<String, String>) {
+    let key = |name: &str| match prefix {
+        "" => name.to_string(),
+        _ => format!("{}.{}", prefix, name),
+    };
+    match value {
+        Value::Object(map) => map
+            .into_iter()
+            .for_each(|(name, v)| flatten_value(&key(&name), v, fields)),
+        Value::Null => {
+            fields.insert(key_or_data(prefix), String::new());
+        }
+        Value::String(s) if s.contains(',') || s.contains('"') => {
+            fields.insert(
+                key_or_data(prefix),
+                format!("\"{}\"", s.replace('"', "\"\"")),
+            );
+        }
+        Value::String(s) => {
+            fields.insert(key_or_data(prefix), s);
+        }
+        v => {
+            fields.insert(key_or_data(prefix), v.to_string());
+        }
+    }
+}
+
+fn key_or_data(prefix: &str) -> String {
+    match prefix {
+        "" => "data".to_string(),
+        _ => prefix.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use timespan::DatedData;
+
+    use super::dated_data_to_csv;
+    use crate::df::score::ScoreDfSummary;
+
+    #[test]
+    fn score_summaries_to_csv() {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 12).unwrap();
+        let summary = |average_score| ScoreDfSummary {
+            average_score,
+            duration: 60,
+            min: Some(50.0),
+            max: Some(90.5),
+        };
+        let rows = vec![
+            DatedData {
+                time: day,
+                data: summary(Some(70.0)),
+            },
+            DatedData {
+                time: day.succ_opt().unwrap(),
+                data: summary(None),
+            },
+        ];
+
+        let csv = dated_data_to_csv(&rows).unwrap();
+
+        assert_eq!(
+            csv.lines().collect::<Vec<&str>>(),
+            vec![
+                "date,average_score,duration,max,min",
+                "2023-04-12,70.0,60,90.5,50.0",
+                "2023-04-13,,60,90.5,50.0",
+            ]
+        );
+    }
+}
@@ -2,11 +2,15 @@ use std::ops::Sub;
 
 use num::Num;
 use num::ToPrimitive;
+use polars::prelude::*;
 
 pub struct StatsUtils;
 
-trait Diff<R> {
+pub trait Diff<R> {
+    /// differences between consecutive values
     fn diff(self) -> R;
+    /// nth-order differences, i.e. `diff` applied `n` times
+    fn diff_n(self, n: usize) -> R;
 }
 
 impl<I, T> Diff<Vec<I>> for T
@@ -20,6 +24,10 @@ where
             .map(|x| x[1] - x[0])
             .collect()
     }
+
+    fn diff_n(self, n: usize) -> Vec<I> {
+        (0..n).fold(self.into_iter().collect(), |v: Vec<I>, _| v.diff())
+    }
 }
 
 pub trait Extrema<T> {
@@ -42,6 +50,32 @@ impl Extrema<f64> for Vec<f64> {
     }
 }
 
+/// Stats computed by polars directly on a `Series` instead of a collected `Vec<f64>`.
+pub trait SeriesStats {
+    /// min and max of the finite values, `None` if there are none
+    fn finite_extrema(&self) -> Option<(f64, f64)>;
+    /// q-quantile (0..=1) with linear interpolation, nulls are ignored
+    fn quantile_f64(&self, q: f64) -> Option<f64>;
+}
+
+impl SeriesStats for Series {
+    fn finite_extrema(&self) -> Option<(f64, f64)> {
+        let s = self.cast(&DataType::Float64).ok()?;
+        let ca = s.f64().ok()?;
+        let finite = ca.filter(&ca.is_finite()).ok()?;
+        Some((finite.min()?, finite.max()?))
+    }
+
+    fn quantile_f64(&self, q: f64) -> Option<f64> {
+        let s = self.cast(&DataType::Float64).ok()?;
+        s.f64()
+            .ok()?
+            .quantile(q.clamp(0.0, 1.0), QuantileInterpolOptions::Linear)
+            .ok()
+            .flatten()
+    }
+}
+
 trait Mean<T> {
     fn mean(self) -> T;
 }
@@ -67,3 +101,69 @@ where
         s / count as f64
     }
 }
+
+/// q-quantile (0..=1) with linear interpolation between the closest ranks
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// Rolling q-quantile over a window centered on each value, `q = 0.5` is a median filter.
+/// The window shrinks at the edges.
+pub fn moving_percentile(data: &[f64], window: usize, q: f64) -> Vec<f64> {
+    let before = window.saturating_sub(1) / 2;
+    let after = window.saturating_sub(1) - before;
+    (0..data.len())
+        .map(|i| {
+            let mut w = data[i.saturating_sub(before)..(i + after + 1).min(data.len())].to_vec();
+            w.sort_by(|a, b| a.total_cmp(b));
+            quantile(&w, q)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::*;
+
+    use super::{moving_percentile, quantile, Diff, Extrema, SeriesStats};
+
+    #[test]
+    fn diff_of_integers() {
+        let v: Vec<i64> = vec![1, 4, 9, 16, 25];
+        assert_eq!(v.clone().diff(), vec![3, 5, 7, 9]);
+        assert_eq!(v.clone().diff_n(2), vec![2, 2, 2]);
+        assert_eq!(v.clone().diff_n(0), v);
+        assert!(v.diff_n(5).is_empty());
+    }
+
+    #[test]
+    fn series_stats_match_vec() {
+        let v = (0..100)
+            .map(|x| (x as f64 * 0.7).sin() * 40.0)
+            .collect::<Vec<f64>>();
+        let s = Series::new("", v.clone());
+
+        assert_eq!(s.finite_extrema(), Some(v.extrema()));
+
+        let mut sorted = v.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let native = s.quantile_f64(0.25).unwrap();
+        assert!((native - quantile(&sorted, 0.25)).abs() < 1e-9);
+
+        let with_inf = Series::new("", [Some(1.0), None, Some(f64::INFINITY), Some(-2.0)]);
+        assert_eq!(with_inf.finite_extrema(), Some((-2.0, 1.0)));
+        assert_eq!(Series::new("", [f64::NAN]).finite_extrema(), None);
+    }
+
+    #[test]
+    fn median_filter_removes_spikes() {
+        let data = vec![1.0, 1.0, 10.0, 1.0, 1.0, 2.0, 2.0, 2.0];
+        assert_eq!(
+            moving_percentile(&data, 3, 0.5),
+            vec![1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0]
+        );
+        assert_eq!(moving_percentile(&data, 3, 1.0)[1], 10.0);
+    }
+}